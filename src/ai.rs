@@ -0,0 +1,150 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    WINDOW_SIZE,
+    game::{Ball, Controller, GameRng, MatchConfig, Paddle, PaddleInput, ball_limit},
+    physics::Velocity,
+    sim::Position,
+};
+
+/// How well a computer-controlled paddle plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl Difficulty {
+    pub fn settings(&self) -> AiSettings {
+        match self {
+            Self::Easy => AiSettings { view_distance: 350.0, dead_zone: 20.0, aim_error: 60.0 },
+            Self::Normal => AiSettings { view_distance: 500.0, dead_zone: 10.0, aim_error: 40.0 },
+            Self::Hard => AiSettings { view_distance: WINDOW_SIZE.0, dead_zone: 4.0, aim_error: 20.0 },
+        }
+    }
+}
+
+/// Tunables for a computer-controlled paddle.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AiSettings {
+    /// How close the ball has to get before the AI starts tracking it, in pixels.
+    pub view_distance: f32,
+    /// How close to its target the paddle has to be before the AI stops moving it.
+    pub dead_zone: f32,
+    /// Largest random offset from the predicted intercept that the AI aims for.
+    pub aim_error: f32,
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        Difficulty::default().settings()
+    }
+}
+
+/// Per-paddle AI state.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct AiAim {
+    /// Offset from the predicted intercept that the AI is aiming for on the current approach.
+    offset: f32,
+    /// Horizontal direction the ball was heading in last tick.
+    ball_direction: f32,
+}
+
+/// Predict where the ball will cross `x`, accounting for bounces off walls at `y = ±limit`, if
+/// there are any.
+pub fn predict_intercept(ball_position: Vec2, ball_velocity: Vec2, x: f32, limit: Option<f32>) -> f32 {
+    if ball_velocity.x == 0.0 {
+        return ball_position.y;
+    }
+    let time = (x - ball_position.x) / ball_velocity.x;
+    let y = ball_position.y + ball_velocity.y * time;
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return y,
+    };
+
+    // Fold the straight-line prediction back into the playfield, once for each wall bounce.
+    let span = limit * 2.0;
+    let folded = (y + limit).rem_euclid(span * 2.0);
+    if folded > span {
+        limit - (folded - span)
+    } else {
+        folded - limit
+    }
+}
+
+pub fn ai_input(
+    mut rng: ResMut<GameRng>,
+    config: Res<MatchConfig>,
+    ball_q: Query<(&Position, &Velocity), With<Ball>>,
+    mut paddle_q: Query<(&Position, &Paddle, &Controller, &mut PaddleInput, &mut AiAim)>,
+) {
+    for (paddle_position, paddle, controller, mut input, mut aim) in paddle_q.iter_mut() {
+        let settings = match controller {
+            Controller::Ai(settings) => *settings,
+            _ => continue,
+        };
+        // Only two-sided arenas have walls at the ends of the paddles' lanes for the ball to
+        // bounce off.
+        let limit = if config.four_player { None } else { Some(ball_limit(paddle)) };
+
+        // Work as if every paddle moved up and down around the middle of its lane, by swapping x
+        // and y for the top and bottom.
+        let local = |v: Vec2| if paddle.side.is_top_or_bottom() { Vec2::new(v.y, v.x) } else { v };
+        let middle = Vec2::new(0.0, paddle.lane_middle);
+        let paddle_position = local(paddle_position.0) - middle;
+        let balls = ball_q.iter().map(|(position, velocity)| (local(position.0) - middle, local(velocity.linear)));
+
+        // Follow the closest ball that is heading toward this paddle, otherwise return to the center.
+        let toward_paddle = |(position, velocity): &(Vec2, Vec2)| {
+            (paddle_position.x - position.x).signum() == velocity.x.signum()
+        };
+        let incoming = balls
+            .filter(toward_paddle)
+            .min_by(|(a, _), (b, _)| {
+                let a = (a.x - paddle_position.x).abs();
+                let b = (b.x - paddle_position.x).abs();
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+            });
+
+        let target = match incoming {
+            Some((ball_position, ball_velocity)) => {
+                // Pick a new aiming error every time the ball turns toward us.
+                let direction = ball_velocity.x.signum();
+                if direction != aim.ball_direction {
+                    aim.offset = (rng.f32() * 2.0 - 1.0) * settings.aim_error;
+                }
+                aim.ball_direction = direction;
+
+                if (ball_position.x - paddle_position.x).abs() <= settings.view_distance {
+                    predict_intercept(ball_position, ball_velocity, paddle_position.x, limit) + aim.offset
+                } else {
+                    0.0
+                }
+            }
+            None => {
+                aim.ball_direction = 0.0;
+                0.0
+            }
+        };
+
+        let delta = target - paddle_position.y;
+        input.movement = if delta > settings.dead_zone {
+            1
+        } else if delta < -settings.dead_zone {
+            -1
+        } else {
+            0
+        };
+    }
+}
@@ -0,0 +1,424 @@
+use std::{collections::HashSet, fs, io, path::Path};
+
+use bevy::prelude::*;
+use bevy::math::Mat2;
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::reflect::TypeUuid;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{PADDLE_SIZE, PlayerSide};
+
+/// Thickness of the goal sensors, and how far behind the goal line they sit.
+const GOAL_WIDTH: f32 = 40.0;
+const GOAL_DEPTH: f32 = 20.0;
+/// Thickness of the wall closing off the goal of a side knocked out of a four-player match.
+const GOAL_WALL_WIDTH: f32 = 20.0;
+/// Size of the cells the arena is split into when checking that it's enclosed.
+const ENCLOSURE_CELL_SIZE: f32 = 5.0;
+
+/// Loads arenas from `.arena.json` files, refusing any that don't pass `Arena::validate`.
+#[derive(Default)]
+pub struct ArenaLoader;
+
+impl AssetLoader for ArenaLoader {
+    fn load<'a>(&'a self, bytes: &'a [u8], load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let arena: Arena = serde_json::from_slice(bytes)?;
+            arena.validate().map_err(|e| anyhow::anyhow!("Invalid arena {}: {}", arena.name, e))?;
+            load_context.set_default_asset(LoadedAsset::new(arena));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arena.json"]
+    }
+}
+
+/// Rectangle in arena coordinates, where the middle of the arena is the origin.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ArenaRect {
+    /// Center of the rectangle.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// Anticlockwise rotation about the center, in degrees.
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+impl ArenaRect {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self { position: position.into(), size: size.into(), rotation: 0.0 }
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position.into()
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.size.into()
+    }
+
+    /// Rotation in radians.
+    pub fn rotation(&self) -> f32 {
+        self.rotation.to_radians()
+    }
+
+    /// Where a point is relative to the center, in the rectangle's own unrotated coordinates.
+    pub fn to_local(&self, point: Vec2) -> Vec2 {
+        Mat2::from_angle(-self.rotation()).mul_vec2(point - self.position())
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        let offset = self.to_local(point).abs();
+        let half_size = self.size() / 2.0;
+        offset.x <= half_size.x && offset.y <= half_size.y
+    }
+
+    /// Half the size of the smallest axis-aligned box around the rectangle.
+    /// Whether the rectangle has a positive width and height, and nothing about it is NaN or
+    /// infinite.
+    fn is_well_formed(&self) -> bool {
+        self.size().cmpgt(Vec2::ZERO).all()
+            && self.size().is_finite()
+            && self.position().is_finite()
+            && self.rotation.is_finite()
+    }
+
+    pub fn half_bounds(&self) -> Vec2 {
+        let (sin, cos) = self.rotation().sin_cos();
+        let half_size = self.size() / 2.0;
+        Vec2::new(
+            half_size.x * cos.abs() + half_size.y * sin.abs(),
+            half_size.x * sin.abs() + half_size.y * cos.abs(),
+        )
+    }
+}
+
+/// Opening in the arena's edge that a side defends.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ArenaGoal {
+    pub side: PlayerSide,
+    /// Middle of the goal line.
+    pub position: [f32; 2],
+    /// Length of the goal line.
+    pub width: f32,
+}
+
+impl ArenaGoal {
+    fn across(&self, depth: f32) -> Vec2 {
+        if self.side.is_top_or_bottom() {
+            Vec2::new(self.width, depth)
+        } else {
+            Vec2::new(depth, self.width)
+        }
+    }
+
+    /// Sensor just behind the goal line that the ball scores by touching.
+    pub fn sensor(&self) -> ArenaRect {
+        let position = Vec2::from(self.position) + self.side.direction() * ((GOAL_WIDTH / 2.0) + GOAL_DEPTH);
+        ArenaRect::new(position, self.across(GOAL_WIDTH))
+    }
+
+    /// Wall along the inside of the goal line, put up once the side is out of a four-player match.
+    pub fn wall(&self) -> ArenaRect {
+        let position = Vec2::from(self.position) - self.side.direction() * (GOAL_WALL_WIDTH / 2.0);
+        ArenaRect::new(position, self.across(GOAL_WALL_WIDTH))
+    }
+
+    /// The goal cut down to `fraction` of its width around the middle, and the walls closing off
+    /// the rest of its line.
+    pub fn narrowed(&self, fraction: f32) -> (ArenaGoal, Vec<ArenaRect>) {
+        let goal = ArenaGoal { width: self.width * fraction.min(1.0), ..*self };
+        let gap = (self.width - goal.width) / 2.0;
+        if gap <= 0.0 {
+            return (goal, Vec::new());
+        }
+        let middle = self.wall().position();
+        let walls = [-1.0, 1.0]
+            .into_iter()
+            .map(|end| {
+                let position = middle + self.side.lane() * (end * (goal.width + gap) / 2.0);
+                ArenaRect::new(position, ArenaGoal { width: gap, ..*self }.across(GOAL_WALL_WIDTH))
+            })
+            .collect();
+        (goal, walls)
+    }
+
+    /// Everything from the goal line to the back of the sensor. A ball in here can only go in.
+    pub fn mouth(&self) -> ArenaRect {
+        let depth = GOAL_DEPTH + GOAL_WIDTH;
+        let position = Vec2::from(self.position) + self.side.direction() * (depth / 2.0);
+        ArenaRect::new(position, self.across(depth))
+    }
+}
+
+/// Block that breaks after being hit by the ball enough times, in modes that play with bricks.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ArenaBrick {
+    #[serde(flatten)]
+    pub rect: ArenaRect,
+    /// Hits the brick takes to break.
+    #[serde(default = "default_hit_points")]
+    pub hit_points: u8,
+    /// Points for whoever last hit the ball when it breaks the brick.
+    #[serde(default = "default_brick_points")]
+    pub points: u8,
+    /// Chance from 0 to 1 of the brick dropping a power-up when it breaks.
+    #[serde(default)]
+    pub power_up_chance: f32,
+}
+
+fn default_hit_points() -> u8 {
+    1
+}
+
+fn default_brick_points() -> u8 {
+    1
+}
+
+/// Line a side's paddles move along.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ArenaLane {
+    pub side: PlayerSide,
+    /// Where the side's first paddle starts, in the middle of the lane. Any others start further
+    /// out from the goal.
+    pub position: [f32; 2],
+    /// Length of the lane. Paddles stay entirely within it.
+    pub length: f32,
+}
+
+/// Layout of the playing field: what the ball bounces off, where each side's goal is and where
+/// its paddles move.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "5b1c93e4-6f0e-4d8a-9a51-2c7e0f4b8d13"]
+pub struct Arena {
+    pub name: String,
+    /// Colour behind the arena, as sRGB.
+    #[serde(default)]
+    pub background: [u8; 3],
+    /// Solid edges of the arena.
+    #[serde(default)]
+    pub walls: Vec<ArenaRect>,
+    /// Solid blocks in the way of the ball, drawn differently from the walls.
+    #[serde(default)]
+    pub obstacles: Vec<ArenaRect>,
+    /// Bricks laid in modes that play with them. Not solid in any other mode.
+    #[serde(default)]
+    pub bricks: Vec<ArenaBrick>,
+    pub goals: Vec<ArenaGoal>,
+    pub lanes: Vec<ArenaLane>,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl Arena {
+    /// The original two-sided arena, for when no other one is picked.
+    pub fn classic() -> Self {
+        Self::built_in(include_str!("../assets/arenas/classic.arena.json"))
+    }
+
+    /// A square arena with a goal on every edge, for four-player matches when no other one is
+    /// picked.
+    pub fn four_player() -> Self {
+        Self::built_in(include_str!("../assets/arenas/four-corners.arena.json"))
+    }
+
+    fn built_in(json: &str) -> Self {
+        serde_json::from_str(json).expect("Built-in arena should be valid")
+    }
+
+    /// Load an arena from outside the asset server, refusing it if it isn't valid.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        let arena: Self = serde_json::from_reader(io::BufReader::new(file))?;
+        arena.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(arena)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = fs::File::create(path)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn background(&self) -> Color {
+        let [r, g, b] = self.background;
+        Color::rgb_u8(r, g, b)
+    }
+
+    pub fn goal(&self, side: PlayerSide) -> Option<&ArenaGoal> {
+        self.goals.iter().find(|goal| goal.side == side)
+    }
+
+    pub fn lane(&self, side: PlayerSide) -> Option<&ArenaLane> {
+        self.lanes.iter().find(|lane| lane.side == side)
+    }
+
+    /// Whether the arena has a goal and a lane for each of these sides, and no others.
+    pub fn fits(&self, sides: &[PlayerSide]) -> bool {
+        self.goals.len() == sides.len()
+            && sides.iter().all(|&side| self.goal(side).is_some() && self.lane(side).is_some())
+    }
+
+    /// Why the arena can't be played in, if it can't.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("The arena needs a name.".into());
+        }
+        let rects = self.walls.iter().chain(&self.obstacles);
+        if !rects.clone().all(ArenaRect::is_well_formed) {
+            return Err("Every wall and obstacle needs a positive width and height.".into());
+        }
+        for brick in &self.bricks {
+            if !brick.rect.is_well_formed() {
+                return Err("Every brick needs a positive width and height.".into());
+            }
+            if brick.hit_points == 0 {
+                return Err("Every brick needs to take at least one hit.".into());
+            }
+            if !(0.0..=1.0).contains(&brick.power_up_chance) {
+                return Err("Bricks' chances of dropping a power-up need to be between 0 and 1.".into());
+            }
+        }
+
+        let mut goal_sides = HashSet::new();
+        for goal in &self.goals {
+            if !(goal.width > 0.0 && goal.width.is_finite() && Vec2::from(goal.position).is_finite()) {
+                return Err(format!("The {} goal needs a positive width.", goal.side.label().to_lowercase()));
+            }
+            if !goal_sides.insert(goal.side) {
+                return Err(format!("There's more than one {} goal.", goal.side.label().to_lowercase()));
+            }
+        }
+        let mut lane_sides = HashSet::new();
+        for lane in &self.lanes {
+            if !lane_sides.insert(lane.side) {
+                return Err(format!("There's more than one {} lane.", lane.side.label().to_lowercase()));
+            }
+            if !(lane.length >= PADDLE_SIZE.1 && lane.length.is_finite() && Vec2::from(lane.position).is_finite()) {
+                return Err(format!("The {} lane is shorter than a paddle.", lane.side.label().to_lowercase()));
+            }
+        }
+        if goal_sides != lane_sides {
+            return Err("Every goal needs a lane for its paddles, and every lane a goal.".into());
+        }
+        let classic = [PlayerSide::Left, PlayerSide::Right].into_iter().collect();
+        if goal_sides != classic && goal_sides.len() != 4 {
+            return Err("There has to be a goal on the left and right, and either none or both of the top and bottom.".into());
+        }
+
+        if rects.clone().chain(self.bricks.iter().map(|brick| &brick.rect)).any(|rect| rect.contains(Vec2::ZERO)) {
+            return Err("The middle of the arena, where the ball is served from, is blocked.".into());
+        }
+        self.check_enclosed()
+    }
+
+    /// Make sure the ball can't leave the arena other than through a goal, and can reach every
+    /// goal. Fills the open space outward from the middle, one cell at a time, until it's
+    /// stopped by walls, obstacles and goals.
+    fn check_enclosed(&self) -> Result<(), String> {
+        let solid: Vec<_> = self.walls.iter().chain(&self.obstacles).collect();
+        let mouths: Vec<_> = self.goals.iter().map(|goal| (goal.side, goal.mouth())).collect();
+
+        // Cover everything, with a border of open cells to escape into.
+        let (mut min, mut max) = (Vec2::ZERO, Vec2::ZERO);
+        for rect in solid.iter().copied().chain(mouths.iter().map(|(_, mouth)| mouth)) {
+            min = min.min(rect.position() - rect.half_bounds());
+            max = max.max(rect.position() + rect.half_bounds());
+        }
+        min -= Vec2::splat(ENCLOSURE_CELL_SIZE * 2.0);
+        max += Vec2::splat(ENCLOSURE_CELL_SIZE * 2.0);
+        let columns = ((max.x - min.x) / ENCLOSURE_CELL_SIZE).ceil() as i32;
+        let rows = ((max.y - min.y) / ENCLOSURE_CELL_SIZE).ceil() as i32;
+        let center = |(column, row): (i32, i32)| {
+            min + (Vec2::new(column as f32, row as f32) + 0.5) * ENCLOSURE_CELL_SIZE
+        };
+
+        let start = (((0.0 - min.x) / ENCLOSURE_CELL_SIZE) as i32, ((0.0 - min.y) / ENCLOSURE_CELL_SIZE) as i32);
+        let mut visited = HashSet::from([start]);
+        let mut open = vec![start];
+        let mut reached = HashSet::new();
+        while let Some((column, row)) = open.pop() {
+            if column == 0 || row == 0 || column == columns - 1 || row == rows - 1 {
+                let point = center((column, row));
+                return Err(format!("The ball can get out of the arena near ({:.0}, {:.0}).", point.x, point.y));
+            }
+            for cell in [(column - 1, row), (column + 1, row), (column, row - 1), (column, row + 1)] {
+                if visited.contains(&cell) {
+                    continue;
+                }
+                visited.insert(cell);
+                let point = center(cell);
+                if solid.iter().any(|rect| rect.contains(point)) {
+                    continue;
+                }
+                if let Some((side, _)) = mouths.iter().find(|(_, mouth)| mouth.contains(point)) {
+                    reached.insert(*side);
+                    continue;
+                }
+                open.push(cell);
+            }
+        }
+
+        match self.goals.iter().find(|goal| !reached.contains(&goal.side)) {
+            Some(goal) => Err(format!("The ball can't reach the {} goal.", goal.side.label().to_lowercase())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_arenas_are_valid() {
+        for json in [
+            include_str!("../assets/arenas/brickworks.arena.json"),
+            include_str!("../assets/arenas/classic.arena.json"),
+            include_str!("../assets/arenas/diamonds.arena.json"),
+            include_str!("../assets/arenas/four-corners.arena.json"),
+            include_str!("../assets/arenas/narrow-goals.arena.json"),
+            include_str!("../assets/arenas/pillars.arena.json"),
+        ] {
+            let arena = Arena::built_in(json);
+            assert_eq!(arena.validate(), Ok(()), "{}", arena.name);
+        }
+    }
+
+    #[test]
+    fn gap_in_walls_rejected() {
+        let mut arena = Arena::classic();
+        arena.walls[0].size[0] = 700.0;
+        assert!(arena.validate().unwrap_err().contains("can get out"));
+
+        // The right goal is walled off from the middle.
+        let mut arena = Arena::classic();
+        arena.obstacles.push(ArenaRect::new(Vec2::new(200.0, 0.0), Vec2::new(20.0, 600.0)));
+        assert!(arena.validate().unwrap_err().contains("can't reach the right goal"));
+    }
+
+    #[test]
+    fn bad_sizes_rejected() {
+        let broken: [fn(&mut Arena); 8] = [
+            |arena| arena.walls[0].size[1] = 0.0,
+            |arena| arena.walls[0].size[0] = f32::NAN,
+            |arena| arena.walls[1].position[0] = f32::INFINITY,
+            |arena| arena.bricks[0].rect.size[0] = -24.0,
+            |arena| arena.bricks[0].rect.size[1] = f32::NAN,
+            |arena| arena.goals[0].width = f32::NAN,
+            |arena| arena.lanes[1].length = f32::NAN,
+            |arena| arena.lanes[1].length = 10.0,
+        ];
+        for (i, break_arena) in broken.into_iter().enumerate() {
+            let mut arena = Arena::classic();
+            break_arena(&mut arena);
+            assert!(arena.validate().is_err(), "arena {} should be rejected", i);
+        }
+    }
+}
@@ -1,12 +1,16 @@
 use bevy::prelude::*;
 use bevy_kira_audio::{AudioPlugin, AudioSource};
 
+use crate::arena::{Arena, ArenaLoader};
+
 pub struct AssetsPlugin;
 
 impl Plugin for AssetsPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_plugin(AudioPlugin)
+            .add_asset::<Arena>()
+            .init_asset_loader::<ArenaLoader>()
             .init_resource::<Assets>()
             .add_startup_system(load_assets);
     }
@@ -16,6 +20,8 @@ impl Plugin for AssetsPlugin {
 pub struct Assets {
     pub font: Handle<Font>,
     pub bounce: Handle<AudioSource>,
+    /// Every arena in the `arenas` folder.
+    pub arenas: Vec<Handle<Arena>>,
 }
 
 pub fn load_assets(
@@ -24,4 +30,8 @@ pub fn load_assets(
 ) {
     assets.font = server.load("fonts/SourceSansPro-Regular.ttf");
     assets.bounce = server.load("sounds/bounce.wav");
+    match server.load_folder("arenas") {
+        Ok(handles) => assets.arenas = handles.into_iter().map(|handle| handle.typed()).collect(),
+        Err(e) => warn!("Failed to load arenas: {:?}", e),
+    }
 }
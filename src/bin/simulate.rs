@@ -0,0 +1,263 @@
+//! Plays AI-vs-AI matches without a window or audio, as fast as possible, and prints aggregate
+//! statistics as JSON. Useful for tuning the AI and game balance.
+
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use bevy::input::InputPlugin;
+use clap::{CommandFactory, ErrorKind, Parser};
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use bevy_pong::{
+    AppState, GamePlugins,
+    ai::{AiSettings, Difficulty},
+    assets::Assets,
+    game::{MAX_TEAM_SIZE, GameState, MatchConfig, MatchMode, PlayerSide},
+    power_up::PowerUpSettings,
+    sim::{SimulationAppExt, SimulationClock},
+    stats::{MatchStats, ServeSideStats},
+};
+
+/// Simulate AI-vs-AI matches and print statistics as JSON.
+#[derive(Debug, Parser)]
+#[clap(version)]
+struct Args {
+    /// Number of matches to play.
+    #[clap(long, short = 'n', value_parser, default_value = "100")]
+    matches: u32,
+    /// Preset for the left AI.
+    #[clap(long, value_enum, value_parser, default_value = "normal")]
+    left: Difficulty,
+    /// Preset for the right AI.
+    #[clap(long, value_enum, value_parser, default_value = "normal")]
+    right: Difficulty,
+    /// JSON file with "left" and/or "right" AI settings, replacing the presets.
+    #[clap(long, value_parser, value_name = "FILE")]
+    settings: Option<PathBuf>,
+    /// Score needed to win a match.
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..), default_value = "11")]
+    points_to_win: u8,
+    /// Number of paddles on each side.
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..=MAX_TEAM_SIZE as i64), default_value = "1")]
+    team_size: u8,
+    /// Seed for the first match. Each following match uses the next seed.
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+    /// Seconds between power-ups appearing in the arena. Matches are played without any when 0.
+    #[clap(long, value_parser, value_name = "SECONDS", default_value = "0")]
+    power_ups: u32,
+    /// Give up on a match that hasn't finished after this many ticks.
+    #[clap(long, value_parser, default_value = "36000")]
+    max_ticks: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SettingsFile {
+    left: Option<AiSettings>,
+    right: Option<AiSettings>,
+}
+
+struct MatchResult {
+    winner: Option<PlayerSide>,
+    left_score: u8,
+    right_score: u8,
+    ticks: u64,
+    stats: MatchStats,
+}
+
+/// Matches still to play, and the results of those already played.
+struct Batch {
+    remaining: u32,
+    next_seed: u64,
+    max_ticks: u64,
+    results: Vec<MatchResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct SideSummary {
+    settings: AiSettings,
+    wins: u32,
+    win_rate: f32,
+    points: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    matches: u32,
+    /// Matches that hit `--max-ticks` before anyone won.
+    unfinished: u32,
+    first_seed: u64,
+    left: SideSummary,
+    right: SideSummary,
+    /// Average number of paddle hits per point.
+    average_rally_length: f32,
+    longest_rally: u32,
+    max_ball_speed: f32,
+    average_match_seconds: f32,
+    served_left: ServeSideStats,
+    served_right: ServeSideStats,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let settings = match &args.settings {
+        Some(path) => {
+            let parsed = fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| serde_json::from_str::<SettingsFile>(&text).map_err(|e| e.to_string()));
+            parsed.unwrap_or_else(|e| {
+                Args::command()
+                    .error(ErrorKind::Io, format!("Failed to load settings {}: {}", path.display(), e))
+                    .exit()
+            })
+        }
+        None => SettingsFile::default(),
+    };
+    let ai = [
+        settings.left.unwrap_or_else(|| args.left.settings()),
+        settings.right.unwrap_or_else(|| args.right.settings()),
+    ];
+    let first_seed = args.seed.unwrap_or_else(|| fastrand::u64(..));
+
+    let mut app = App::new();
+    app.insert_resource(MatchConfig {
+            mode: MatchMode::AiVsAi,
+            ai,
+            seed: None,
+            points_to_win: Some(args.points_to_win),
+            team_size: args.team_size,
+            power_ups: PowerUpSettings { interval: args.power_ups, ..default() },
+            ..default()
+        })
+        .insert_resource(Batch {
+            remaining: args.matches,
+            next_seed: first_seed,
+            max_ticks: args.max_ticks,
+            results: Vec::new(),
+        })
+        .init_resource::<Assets>()
+        .add_plugins(MinimalPlugins)
+        .add_plugin(InputPlugin)
+        .add_loopless_state(AppState::MainMenu)
+        .add_plugins(GamePlugins)
+        .add_system(start_next_match.run_not_in_state(AppState::InGame))
+        .add_simulation_system(give_up_match
+            .run_in_state(AppState::InGame)
+            .after("check_match_over"))
+        .add_exit_system(AppState::InGame, record_result);
+    app.world.resource_mut::<SimulationClock>().unthrottled = true;
+
+    while !finished(&app.world) {
+        app.update();
+    }
+
+    let summary = summarize(&app.world.resource::<Batch>().results, ai, first_seed);
+    println!("{}", serde_json::to_string_pretty(&summary).expect("Failed to serialize summary"));
+}
+
+fn finished(world: &World) -> bool {
+    let batch = world.resource::<Batch>();
+    let playing = world
+        .get_resource::<CurrentState<AppState>>()
+        .map_or(true, |state| state.0 == AppState::InGame);
+    let transitioning = world.contains_resource::<NextState<AppState>>();
+    batch.remaining == 0 && !playing && !transitioning
+}
+
+fn start_next_match(
+    mut commands: Commands,
+    mut batch: ResMut<Batch>,
+    mut config: ResMut<MatchConfig>,
+) {
+    if batch.remaining == 0 {
+        return;
+    }
+    batch.remaining -= 1;
+    config.seed = Some(batch.next_seed);
+    batch.next_seed = batch.next_seed.wrapping_add(1);
+    commands.insert_resource(NextState(AppState::InGame));
+}
+
+fn give_up_match(
+    mut commands: Commands,
+    batch: Res<Batch>,
+    game_state: Res<GameState>,
+) {
+    if game_state.tick >= batch.max_ticks {
+        commands.insert_resource(NextState(AppState::MatchOver));
+    }
+}
+
+fn record_result(
+    mut batch: ResMut<Batch>,
+    game_state: Res<GameState>,
+    config: Res<MatchConfig>,
+    stats: Res<MatchStats>,
+) {
+    let points_to_win = config.points_to_win.unwrap_or(u8::MAX);
+    let (left_score, right_score) = (game_state.score(PlayerSide::Left), game_state.score(PlayerSide::Right));
+    let winner = if left_score >= points_to_win {
+        Some(PlayerSide::Left)
+    } else if right_score >= points_to_win {
+        Some(PlayerSide::Right)
+    } else {
+        None
+    };
+    batch.results.push(MatchResult {
+        winner,
+        left_score,
+        right_score,
+        ticks: game_state.tick,
+        stats: stats.clone(),
+    });
+}
+
+fn summarize(results: &[MatchResult], ai: [AiSettings; 2], first_seed: u64) -> Summary {
+    let matches = results.len() as u32;
+    let ratio = |count: u32, total: u32| if total == 0 { 0.0 } else { count as f32 / total as f32 };
+    let side = |side: PlayerSide, settings: AiSettings| {
+        let wins = results.iter().filter(|result| result.winner == Some(side)).count() as u32;
+        let points = results
+            .iter()
+            .map(|result| match side {
+                PlayerSide::Left => result.left_score as u32,
+                _ => result.right_score as u32,
+            })
+            .sum();
+        SideSummary { settings, wins, win_rate: ratio(wins, matches), points }
+    };
+    let serve_side = |side: PlayerSide| {
+        results.iter().fold(ServeSideStats::default(), |total, result| {
+            let stats = result.stats.serve_side(side);
+            ServeSideStats {
+                points: total.points + stats.points,
+                won_by_receiver: total.won_by_receiver + stats.won_by_receiver,
+            }
+        })
+    };
+
+    let rallies = results.iter().flat_map(|result| result.stats.rallies.iter().copied());
+    let (rally_count, rally_hits) = rallies.fold((0, 0), |(count, hits), rally| (count + 1, hits + rally));
+    let total_ticks: u64 = results.iter().map(|result| result.ticks).sum();
+
+    Summary {
+        matches,
+        unfinished: results.iter().filter(|result| result.winner.is_none()).count() as u32,
+        first_seed,
+        left: side(PlayerSide::Left, ai[0]),
+        right: side(PlayerSide::Right, ai[1]),
+        average_rally_length: ratio(rally_hits, rally_count),
+        longest_rally: results.iter().map(|result| result.stats.longest_rally()).max().unwrap_or(0),
+        max_ball_speed: results.iter().map(|result| result.stats.top_ball_speed).fold(0.0, f32::max),
+        average_match_seconds: if matches == 0 {
+            0.0
+        } else {
+            total_ticks as f32 / matches as f32 / bevy_pong::sim::TICK_RATE as f32
+        },
+        served_left: serve_side(PlayerSide::Left),
+        served_right: serve_side(PlayerSide::Right),
+    }
+}
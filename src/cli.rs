@@ -0,0 +1,405 @@
+use std::{io, ops::RangeInclusive, path::PathBuf};
+
+use bevy::prelude::*;
+use bevy::window::WindowMode;
+use clap::{CommandFactory, ErrorKind, Parser};
+
+use crate::{
+    WINDOW_SIZE, AppState,
+    ai::Difficulty,
+    arena::Arena,
+    event_log::EventLogSettings,
+    game::{BALL_MAX_SPEED, MAX_TEAM_SIZE, Handicap, MatchConfig, MatchMode, PaddleMovement, PlayerSide, ServeStyle},
+    mode::{GameModes, classic::ClassicMode, practice::PracticeMode, survival::SurvivalMode},
+    net::{NetConditions, NetLobby},
+    power_up::PowerUpSettings,
+};
+
+/// Pong! Start a match straight from the command line by passing --mode or --replay, or a network
+/// match with --host or --join.
+#[derive(Debug, Parser)]
+#[clap(version)]
+pub struct Args {
+    /// Skip the main menu and start a match with these players.
+    #[clap(long, value_enum, value_parser, group = "start")]
+    pub mode: Option<MatchMode>,
+    /// How well computer players play. Only valid with `--mode ai` or `--mode ai-vs-ai`.
+    #[clap(long, value_enum, value_parser, requires = "mode")]
+    pub difficulty: Option<Difficulty>,
+    /// Seed for the match's randomness, to get the same serves every time. Only valid with
+    /// `--mode` or `--host`.
+    #[clap(long, value_parser, requires = "start")]
+    pub seed: Option<u64>,
+    /// Score needed to win. Without this, the match goes on forever. Only valid with `--mode` or
+    /// `--host`.
+    #[clap(long, requires = "start", value_parser = clap::value_parser!(u8).range(1..))]
+    pub points_to_win: Option<u8>,
+    /// Number of paddles on each side, for doubles. Only valid with `--mode`.
+    #[clap(long, requires = "mode", value_parser = clap::value_parser!(u8).range(1..=MAX_TEAM_SIZE as i64))]
+    pub team_size: Option<u8>,
+    /// Play with a paddle on every edge, each side guarding its own goal until it runs out of
+    /// lives. Only valid with `--mode`.
+    #[clap(long, action, requires = "mode", conflicts_with = "team-size")]
+    pub four_player: bool,
+    /// Play alone against a wall, keeping the ball going for as long as possible. Only valid with
+    /// `--mode`.
+    #[clap(long, action, requires = "mode", conflicts_with_all = &["team-size", "four-player"])]
+    pub solo: bool,
+    /// Rules to play by, like `classic`, `survival`, `timed`, `breakout` or `multiball`. Defaults
+    /// to classic, survival with `--four-player` or practice with `--solo`. Only valid with
+    /// `--mode`.
+    #[clap(long, value_parser, value_name = "MODE", requires = "mode")]
+    pub rules: Option<String>,
+    /// Goals each side can let in before it's out, with rules that count lives. Only valid with
+    /// `--mode`.
+    #[clap(long, requires = "mode", value_parser = clap::value_parser!(u8).range(1..))]
+    pub lives: Option<u8>,
+    /// Seconds of play, with rules that have a clock. Only valid with `--mode`.
+    #[clap(long, value_name = "SECONDS", requires = "mode", value_parser = clap::value_parser!(u32).range(1..))]
+    pub time_limit: Option<u32>,
+    /// Seconds between power-ups appearing in the arena, or 0 for none. Only valid with `--mode`.
+    #[clap(long, value_name = "SECONDS", requires = "mode", value_parser = clap::value_parser!(u32))]
+    pub power_ups: Option<u32>,
+    /// Speed the ball is served at. Only valid with `--mode`.
+    #[clap(long, requires = "mode", value_parser = float_in(1.0..=BALL_MAX_SPEED))]
+    pub ball_speed: Option<f32>,
+    /// Speed the ball gains every time it's returned. Only valid with `--mode`.
+    #[clap(long, requires = "mode", value_parser = float_in(0.0..=BALL_MAX_SPEED))]
+    pub speed_increment: Option<f32>,
+    /// Degrees a second the ball curves by after being hit by a paddle moving at full speed.
+    /// Only valid with `--mode`.
+    #[clap(long, value_name = "DEGREES", requires = "mode", value_parser = float_in(0.0..=180.0))]
+    pub spin: Option<f32>,
+    /// How paddles respond to their controls. Only valid with `--mode`.
+    #[clap(long, value_enum, value_parser, requires = "mode")]
+    pub paddle_movement: Option<PaddleMovement>,
+    /// Where the ball is served from after every point. Only valid with `--mode`.
+    #[clap(long, value_enum, value_parser, requires = "mode")]
+    pub serve: Option<ServeStyle>,
+    /// Seconds players get to serve off their paddle before the ball goes anyway. Only valid
+    /// with `--mode`.
+    #[clap(long, value_name = "SECONDS", requires = "mode", value_parser = clap::value_parser!(u32).range(1..))]
+    pub serve_timeout: Option<u32>,
+    /// Seconds the ball waits in the middle before every serve from there. Only valid with
+    /// `--mode`.
+    #[clap(long, value_name = "SECONDS", requires = "mode", value_parser)]
+    pub countdown: Option<u32>,
+    /// Put paddles back in the middle of their lanes after every point. Only valid with `--mode`.
+    #[clap(long, action, requires = "mode")]
+    pub reset_paddles: bool,
+    /// Handicap for the left or the right, like `left=paddle:0.8,speed:1.2,goal:0.5,points:2`.
+    /// Paddle length and speed go from 0.5 to 2, goal width from 0.3 to 1 and starting points up
+    /// to 10. Can be given once for each side. Only valid with `--mode`.
+    #[clap(long, value_name = "SIDE=CHANGES", requires = "mode", value_parser = parse_handicap)]
+    pub handicap: Vec<(PlayerSide, Handicap)>,
+    /// Play in the arena from this file instead of the default one. Only valid with `--mode`.
+    #[clap(long, value_parser, value_name = "FILE", requires = "mode")]
+    pub arena: Option<PathBuf>,
+    /// Window width, in pixels.
+    #[clap(long, value_parser = float_in(WINDOW_SIZE_RANGE), conflicts_with = "fullscreen")]
+    pub width: Option<f32>,
+    /// Window height, in pixels.
+    #[clap(long, value_parser = float_in(WINDOW_SIZE_RANGE), conflicts_with = "fullscreen")]
+    pub height: Option<f32>,
+    /// Run in borderless fullscreen.
+    #[clap(long, action)]
+    pub fullscreen: bool,
+    /// Play back a replay file instead of a live match.
+    #[clap(long, value_parser, value_name = "FILE", conflicts_with_all = &["mode", "difficulty", "seed", "points-to-win", "team-size", "four-player", "solo", "rules", "lives", "time-limit", "power-ups", "ball-speed", "speed-increment", "spin", "paddle-movement", "serve", "serve-timeout", "countdown", "reset-paddles", "handicap", "arena", "record"])]
+    pub replay: Option<PathBuf>,
+    /// Log what happens in every match to a JSON lines file in this directory. Can be toggled
+    /// while running with F2.
+    #[clap(long, value_parser, value_name = "DIR")]
+    pub event_log: Option<PathBuf>,
+    /// Save the first match played to a replay file.
+    #[clap(long, value_parser, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+    /// Host a network match on this UDP port and wait for another player to join.
+    #[clap(long, value_parser, value_name = "PORT", group = "start", conflicts_with_all = &["mode", "difficulty", "replay", "record", "join"])]
+    pub host: Option<u16>,
+    /// Join the network match hosted at this address, like `192.168.1.20:7777`.
+    #[clap(long, value_parser, value_name = "ADDRESS", conflicts_with_all = &["mode", "difficulty", "seed", "points-to-win", "replay", "record"])]
+    pub join: Option<String>,
+    /// Simulated delay added to every network message sent, in milliseconds.
+    #[clap(long, value_parser, value_name = "MS", default_value = "0")]
+    pub net_latency: u32,
+    /// Most simulated random delay added on top of --net-latency, in milliseconds.
+    #[clap(long, value_parser, value_name = "MS", default_value = "0")]
+    pub net_jitter: u32,
+    /// Percentage of network messages to drop.
+    #[clap(long, value_parser, value_name = "PERCENT", default_value = "0")]
+    pub net_loss: f32,
+    /// Percentage of network messages to send twice.
+    #[clap(long, value_parser, value_name = "PERCENT", default_value = "0")]
+    pub net_duplicate: f32,
+    /// Percentage of network messages to deliver out of order.
+    #[clap(long, value_parser, value_name = "PERCENT", default_value = "0")]
+    pub net_reorder: f32,
+}
+
+/// Sizes the window can be given, in pixels.
+const WINDOW_SIZE_RANGE: RangeInclusive<f32> = 1.0..=16384.0;
+
+/// Parse a number in a range, like `clap::value_parser!(u8).range(..)` does for integers.
+fn float_in(range: RangeInclusive<f32>) -> impl Fn(&str) -> Result<f32, String> + Clone + Send + Sync + 'static {
+    move |value| {
+        let number: f32 = value.parse().map_err(|e| format!("{}", e))?;
+        // Also turns away NaN, which isn't in any range.
+        if range.contains(&number) {
+            Ok(number)
+        } else {
+            Err(format!("{} is not in {}..={}", value, range.start(), range.end()))
+        }
+    }
+}
+
+/// Parse a side's handicap, like `left=paddle:0.8,points:2`. Anything left out stays even.
+fn parse_handicap(value: &str) -> Result<(PlayerSide, Handicap), String> {
+    let (side, changes) = value
+        .split_once('=')
+        .ok_or_else(|| "expected SIDE=CHANGES, like left=paddle:0.8,points:2".to_string())?;
+    let side = match side {
+        "left" => PlayerSide::Left,
+        "right" => PlayerSide::Right,
+        _ => return Err(format!("{} can't have a handicap, only left or right can", side)),
+    };
+    let mut handicap = Handicap::default();
+    for change in changes.split(',') {
+        let (name, amount) = change
+            .split_once(':')
+            .ok_or_else(|| format!("expected NAME:AMOUNT, got {}", change))?;
+        match name {
+            "paddle" => handicap.paddle_length = float_in(Handicap::PADDLE_RANGE)(amount)?,
+            "speed" => handicap.paddle_speed = float_in(Handicap::PADDLE_RANGE)(amount)?,
+            "goal" => handicap.goal_width = float_in(Handicap::GOAL_RANGE)(amount)?,
+            "points" => {
+                handicap.starting_points = match amount.parse() {
+                    Ok(points) if points <= Handicap::MAX_STARTING_POINTS => points,
+                    _ => return Err(format!("{} is not in 0..={}", amount, Handicap::MAX_STARTING_POINTS)),
+                };
+            }
+            _ => return Err(format!("unknown handicap {}, try paddle, speed, goal or points", name)),
+        }
+    }
+    Ok((side, handicap))
+}
+
+impl Args {
+    /// Parse the command line, exiting with a usage error if the arguments don't make sense together.
+    pub fn parse_validated() -> Self {
+        let args = Self::parse();
+
+        if args.difficulty.is_some() && !matches!(args.mode, Some(MatchMode::Ai | MatchMode::AiVsAi)) {
+            Self::command()
+                .error(ErrorKind::ArgumentConflict, "--difficulty can only be used with --mode ai or ai-vs-ai")
+                .exit();
+        }
+        let percentages = [
+            ("--net-loss", args.net_loss),
+            ("--net-duplicate", args.net_duplicate),
+            ("--net-reorder", args.net_reorder),
+        ];
+        for (name, percentage) in percentages {
+            if !(0.0..=100.0).contains(&percentage) {
+                Self::command()
+                    .error(ErrorKind::InvalidValue, format!("{} must be between 0 and 100", name))
+                    .exit();
+            }
+        }
+        for side in [PlayerSide::Left, PlayerSide::Right] {
+            if args.handicap.iter().filter(|(handicap_side, _)| *handicap_side == side).count() > 1 {
+                Self::command()
+                    .error(ErrorKind::ArgumentConflict, format!("--handicap can only be given once for the {}", side.label().to_lowercase()))
+                    .exit();
+            }
+        }
+        if args.solo && args.handicap.iter().any(|(side, _)| *side == PlayerSide::Right) {
+            Self::command()
+                .error(ErrorKind::ArgumentConflict, "--solo matches don't have a right side to handicap")
+                .exit();
+        }
+        args
+    }
+
+    /// Report a problem with an argument's value and exit.
+    pub fn exit_with_error(message: impl std::fmt::Display) -> ! {
+        Self::command().error(ErrorKind::Io, message).exit()
+    }
+
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: "Pong!".into(),
+            width: self.width.unwrap_or(WINDOW_SIZE.0),
+            height: self.height.unwrap_or(WINDOW_SIZE.1),
+            mode: if self.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed },
+            resizable: false,
+            ..default()
+        }
+    }
+
+    pub fn initial_state(&self) -> AppState {
+        if self.mode.is_some() || self.replay.is_some() {
+            AppState::InGame
+        } else {
+            AppState::MainMenu
+        }
+    }
+
+    /// Start hosting or joining a network match, if asked to.
+    pub fn net_lobby(&self) -> Option<io::Result<NetLobby>> {
+        match (self.host, &self.join) {
+            (Some(port), _) => Some(NetLobby::host(port)),
+            (None, Some(address)) => Some(NetLobby::join(address)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn event_log_settings(&self) -> EventLogSettings {
+        match &self.event_log {
+            Some(directory) => EventLogSettings { enabled: true, directory: directory.clone() },
+            None => EventLogSettings::default(),
+        }
+    }
+
+    pub fn net_conditions(&self) -> NetConditions {
+        NetConditions {
+            latency: self.net_latency,
+            jitter: self.net_jitter,
+            loss: self.net_loss,
+            duplicate: self.net_duplicate,
+            reorder: self.net_reorder,
+        }
+    }
+
+    pub fn match_config(&self) -> MatchConfig {
+        let arena = match &self.arena {
+            Some(path) => Arena::load(path).unwrap_or_else(|e| {
+                Self::exit_with_error(format!("Failed to load arena {}: {}", path.display(), e))
+            }),
+            None if self.four_player => Arena::four_player(),
+            None => Arena::classic(),
+        };
+        let defaults = MatchConfig::default();
+        let config = MatchConfig {
+            mode: self.mode.unwrap_or_default(),
+            ai: [self.difficulty.unwrap_or_default().settings(); 2],
+            seed: self.seed,
+            points_to_win: self.points_to_win,
+            team_size: self.team_size.unwrap_or(1),
+            four_player: self.four_player,
+            solo: self.solo,
+            game_mode: match &self.rules {
+                Some(rules) => rules.clone(),
+                None if self.four_player => SurvivalMode::ID.into(),
+                None if self.solo => PracticeMode::ID.into(),
+                None => ClassicMode::ID.into(),
+            },
+            lives: self.lives.unwrap_or(defaults.lives),
+            time_limit: self.time_limit.unwrap_or(defaults.time_limit),
+            multi_ball: defaults.multi_ball,
+            power_ups: PowerUpSettings {
+                interval: self.power_ups.unwrap_or(defaults.power_ups.interval),
+                ..defaults.power_ups
+            },
+            ball_speed: self.ball_speed.unwrap_or(defaults.ball_speed),
+            speed_increment: self.speed_increment.unwrap_or(defaults.speed_increment),
+            spin: self.spin.unwrap_or_default(),
+            spin_decay: defaults.spin_decay,
+            paddle_movement: self.paddle_movement.unwrap_or_default(),
+            inertia: defaults.inertia,
+            serve: self.serve.unwrap_or_default(),
+            serve_timeout: self.serve_timeout.unwrap_or(defaults.serve_timeout),
+            countdown: self.countdown.unwrap_or_default(),
+            reset_paddles: self.reset_paddles,
+            handicaps: self.handicaps(),
+            arena,
+            players: Vec::new(),
+        };
+        if !config.arena.fits(config.arena_sides()) {
+            let players = if config.four_player { "four-player" } else { "two-sided" };
+            Self::exit_with_error(format!("The {} arena doesn't have the goals for a {} match", config.arena.name, players));
+        }
+        config
+    }
+
+    fn handicaps(&self) -> [Handicap; 2] {
+        let mut handicaps = <[Handicap; 2]>::default();
+        for &(side, handicap) in &self.handicap {
+            handicaps[side.index()] = handicap;
+        }
+        handicaps
+    }
+
+    /// Make sure a match started from the command line can be played by the rules asked for.
+    /// Only possible once every game mode's plugin has been added.
+    pub fn check_rules(&self, config: &MatchConfig, modes: &GameModes) {
+        if self.mode.is_none() || modes.picked(config).is_some() {
+            return;
+        }
+        let playable: Vec<_> = modes.supporting(config).map(|mode| mode.id()).collect();
+        Self::exit_with_error(format!("Can't play {} rules in this match, try one of: {}", config.game_mode, playable.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> clap::Result<Args> {
+        Args::try_parse_from(["bevy-pong"].iter().chain(args))
+    }
+
+    #[test]
+    fn ball_settings_in_range() {
+        let args = parse(&["--mode", "local", "--ball-speed", "500", "--speed-increment", "0", "--spin", "90"]).unwrap();
+        assert_eq!(args.ball_speed, Some(500.0));
+        assert_eq!(args.speed_increment, Some(0.0));
+        assert_eq!(args.spin, Some(90.0));
+    }
+
+    #[test]
+    fn ball_settings_out_of_range() {
+        for (name, value) in [
+            ("--ball-speed", "-400"),
+            ("--ball-speed", "0"),
+            ("--ball-speed", "NaN"),
+            ("--speed-increment", "-10"),
+            ("--speed-increment", "inf"),
+            ("--spin", "-45"),
+            ("--spin", "NaN"),
+            ("--width", "0"),
+            ("--width", "NaN"),
+            ("--height", "-600"),
+            ("--height", "inf"),
+        ] {
+            // Joined with `=`, so negative numbers aren't taken for flags.
+            let arg = format!("{}={}", name, value);
+            let error = parse(&["--mode", "local", &arg]).expect_err(&format!("{} should be rejected", arg));
+            assert_eq!(error.kind(), ErrorKind::ValueValidation);
+        }
+    }
+
+    #[test]
+    fn power_up_interval() {
+        // Off unless asked for.
+        let built_in = PowerUpSettings::default();
+        assert_eq!(built_in.interval, 0);
+        let config = parse(&["--mode", "local"]).unwrap().match_config();
+        assert_eq!(config.power_ups, built_in);
+        let config = parse(&["--mode", "local", "--power-ups", "20"]).unwrap().match_config();
+        assert_eq!(config.power_ups, PowerUpSettings { interval: 20, ..built_in });
+    }
+
+    #[test]
+    fn handicaps() {
+        let args = parse(&["--mode", "local", "--handicap", "left=paddle:0.8,points:2", "--handicap", "right=goal:0.5"]).unwrap();
+        let handicaps = args.match_config().handicaps;
+        assert_eq!(handicaps[PlayerSide::Left.index()], Handicap { paddle_length: 0.8, starting_points: 2, ..default() });
+        assert_eq!(handicaps[PlayerSide::Right.index()], Handicap { goal_width: 0.5, ..default() });
+
+        for value in ["top=paddle:0.8", "left", "left=paddle", "left=paddle:3", "left=goal:NaN", "left=points:11", "left=size:1"] {
+            let error = parse(&["--mode", "local", "--handicap", value]).expect_err(&format!("{} should be rejected", value));
+            assert_eq!(error.kind(), ErrorKind::ValueValidation);
+        }
+    }
+}
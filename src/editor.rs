@@ -0,0 +1,683 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy_egui::{egui, EguiContext};
+use iyes_loopless::prelude::*;
+
+use crate::{
+    ALLOW_EXPORT, AppState,
+    arena::{Arena, ArenaGoal, ArenaLane, ArenaRect},
+    assets::Assets,
+    game::{ALL_SIDES, MatchConfig, OBSTACLE_COLOR, PADDLE_SIZE, PlayerSide, arena_camera, pan_and_zoom},
+    mode::GameModes,
+};
+
+/// Folder arenas are saved to, which the asset server loads them from.
+const ARENAS_DIR: &str = "assets/arenas";
+/// Length of the goal line of a newly placed goal.
+const NEW_GOAL_WIDTH: f32 = 200.0;
+/// How far in from the goal line, and from each end of it, a new goal's lane is.
+const LANE_INSET: f32 = 20.0;
+/// How much more of the arena the editor shows than a match, so the goals behind the edges can
+/// be seen.
+const EDITOR_ZOOM: f32 = 1.25;
+const SELECTED_COLOR: Color = Color::YELLOW;
+const LANE_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.3);
+const GRID_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.06);
+/// Fewest arena units between grid lines that are drawn, however fine the grid.
+const MIN_GRID_LINE_SPACING: f32 = 10.0;
+
+/// Screen for laying out arenas with the mouse, trying them out and saving them. Needs
+/// `EguiPlugin`.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<EditorState>()
+            .add_enter_system(AppState::Editor, setup_editor)
+            .add_enter_system(AppState::Editor, finish_test_play)
+            .add_exit_system(AppState::Editor, despawn_editor)
+            .add_enter_system(AppState::MainMenu, finish_test_play)
+            .add_system(editor_ui.run_in_state(AppState::Editor).label("editor_ui"))
+            .add_system(edit_with_mouse.run_in_state(AppState::Editor).label("edit_with_mouse").after("editor_ui"))
+            .add_system(draw_arena.run_in_state(AppState::Editor).after("edit_with_mouse"))
+            .add_system(test_play_ui
+                .run_in_state(AppState::InGame)
+                .run_if_resource_exists::<TestPlay>());
+    }
+}
+
+/// Present while a match is being played to try out an arena from the editor. Holds the match
+/// settings to put back afterward.
+pub struct TestPlay {
+    previous_config: MatchConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    Select,
+    Wall,
+    Obstacle,
+    Goal,
+}
+
+/// Part of the arena picked with the mouse, by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    Wall(usize),
+    Obstacle(usize),
+    Goal(usize),
+}
+
+/// What dragging with the left mouse button is doing to the selection.
+#[derive(Debug, Clone, Copy)]
+enum Drag {
+    /// Moving it, keeping the mouse the same offset from its middle.
+    Move { offset: Vec2 },
+    /// Changing its size, keeping its middle in place.
+    Resize,
+    /// Stretching a new wall or obstacle out from where the drag started.
+    Draw { start: Vec2 },
+}
+
+/// The arena being edited, and how the editor is being used on it.
+pub struct EditorState {
+    arena: Arena,
+    tool: Tool,
+    /// Side a goal is placed for with `Tool::Goal`.
+    goal_side: PlayerSide,
+    selected: Option<Selection>,
+    drag: Option<Drag>,
+    snap: bool,
+    grid_size: f32,
+    /// File to save to in `ARENAS_DIR`, without the extension.
+    file_name: String,
+    /// How the last save or test play went.
+    status: Option<String>,
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        Self {
+            arena: Arena::classic(),
+            tool: Tool::Select,
+            goal_side: PlayerSide::Left,
+            selected: None,
+            drag: None,
+            snap: true,
+            grid_size: 10.0,
+            file_name: String::new(),
+            status: None,
+        }
+    }
+}
+
+impl EditorState {
+    fn snap(&self, point: Vec2) -> Vec2 {
+        if self.snap {
+            (point / self.grid_size).round() * self.grid_size
+        } else {
+            point
+        }
+    }
+
+    /// Snap a size to the grid, without letting it shrink to nothing.
+    fn snap_size(&self, size: f32) -> f32 {
+        if self.snap {
+            (size / self.grid_size).round().max(1.0) * self.grid_size
+        } else {
+            size.max(1.0)
+        }
+    }
+
+    fn rect_mut(&mut self, selection: Selection) -> Option<&mut ArenaRect> {
+        match selection {
+            Selection::Wall(index) => self.arena.walls.get_mut(index),
+            Selection::Obstacle(index) => self.arena.obstacles.get_mut(index),
+            Selection::Goal(_) => None,
+        }
+    }
+
+    /// A goal, and the lane its paddles move in.
+    fn goal_mut(&mut self, index: usize) -> Option<(&mut ArenaGoal, Option<&mut ArenaLane>)> {
+        let goal = self.arena.goals.get_mut(index)?;
+        let lane = self.arena.lanes.iter_mut().find(|lane| lane.side == goal.side);
+        Some((goal, lane))
+    }
+
+    /// Topmost part of the arena under a point. Goals are drawn over obstacles, and obstacles
+    /// over walls.
+    fn hit(&self, point: Vec2) -> Option<Selection> {
+        let goal = self.arena.goals.iter().rposition(|goal| goal.mouth().contains(point));
+        let obstacle = self.arena.obstacles.iter().rposition(|rect| rect.contains(point));
+        let wall = self.arena.walls.iter().rposition(|rect| rect.contains(point));
+        goal.map(Selection::Goal)
+            .or_else(|| obstacle.map(Selection::Obstacle))
+            .or_else(|| wall.map(Selection::Wall))
+    }
+
+    fn position(&self, selection: Selection) -> Option<Vec2> {
+        match selection {
+            Selection::Wall(index) => self.arena.walls.get(index).map(ArenaRect::position),
+            Selection::Obstacle(index) => self.arena.obstacles.get(index).map(ArenaRect::position),
+            Selection::Goal(index) => self.arena.goals.get(index).map(|goal| goal.position.into()),
+        }
+    }
+
+    /// Move the middle of the selection to a point, taking a goal's lane along with it.
+    fn move_to(&mut self, selection: Selection, point: Vec2) {
+        if let Some(rect) = self.rect_mut(selection) {
+            rect.position = point.into();
+        } else if let Selection::Goal(index) = selection {
+            if let Some((goal, lane)) = self.goal_mut(index) {
+                let offset = point - Vec2::from(goal.position);
+                goal.position = point.into();
+                if let Some(lane) = lane {
+                    lane.position = (Vec2::from(lane.position) + offset).into();
+                }
+            }
+        }
+    }
+
+    /// Resize the selection so that its edge, or the end of a goal line, is at a point.
+    fn resize_to(&mut self, selection: Selection, point: Vec2) {
+        let middle = match self.position(selection) {
+            Some(middle) => middle,
+            None => return,
+        };
+        // Measure along a rotated wall's own sides.
+        let offset = match self.rect_mut(selection) {
+            Some(rect) => rect.to_local(point),
+            None => point - middle,
+        };
+        let half_size = offset.abs();
+        let size = Vec2::new(self.snap_size(half_size.x * 2.0), self.snap_size(half_size.y * 2.0));
+        if let Some(rect) = self.rect_mut(selection) {
+            rect.size = size.into();
+        } else if let Selection::Goal(index) = selection {
+            if let Some((goal, lane)) = self.goal_mut(index) {
+                // The lane grows and shrinks along with the goal line.
+                let width = size.dot(goal.side.lane());
+                if let Some(lane) = lane {
+                    lane.length = (lane.length + width - goal.width).max(PADDLE_SIZE.1);
+                }
+                goal.width = width;
+            }
+        }
+    }
+
+    fn remove(&mut self, selection: Selection) {
+        match selection {
+            Selection::Wall(index) if index < self.arena.walls.len() => {
+                self.arena.walls.remove(index);
+            }
+            Selection::Obstacle(index) if index < self.arena.obstacles.len() => {
+                self.arena.obstacles.remove(index);
+            }
+            Selection::Goal(index) if index < self.arena.goals.len() => {
+                let goal = self.arena.goals.remove(index);
+                self.arena.lanes.retain(|lane| lane.side != goal.side);
+            }
+            _ => {}
+        }
+        self.selected = None;
+        self.drag = None;
+    }
+
+    /// Put the goal for a side at a point, adding it with a lane if the side doesn't have one yet.
+    fn place_goal(&mut self, side: PlayerSide, point: Vec2) {
+        let index = match self.arena.goals.iter().position(|goal| goal.side == side) {
+            Some(index) => {
+                self.move_to(Selection::Goal(index), point);
+                index
+            }
+            None => {
+                self.arena.goals.push(ArenaGoal { side, position: point.into(), width: NEW_GOAL_WIDTH });
+                self.arena.lanes.retain(|lane| lane.side != side);
+                self.arena.lanes.push(ArenaLane {
+                    side,
+                    position: (point - side.direction() * LANE_INSET).into(),
+                    length: NEW_GOAL_WIDTH - LANE_INSET * 2.0,
+                });
+                self.arena.goals.len() - 1
+            }
+        };
+        self.selected = Some(Selection::Goal(index));
+    }
+
+    fn open(&mut self, arena: Arena) {
+        self.file_name = file_name_for(&arena.name);
+        self.arena = arena;
+        self.selected = None;
+        self.drag = None;
+        self.status = None;
+    }
+}
+
+/// File name to save an arena with a given name to, like `my-arena`.
+fn file_name_for(name: &str) -> String {
+    let lower = name.trim().to_lowercase();
+    let words: Vec<_> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        "arena".into()
+    } else {
+        words.join("-")
+    }
+}
+
+/// Marks sprites drawn to show the arena being edited.
+#[derive(Component)]
+struct EditorShape;
+
+fn setup_editor(
+    mut commands: Commands,
+) {
+    let mut camera = arena_camera();
+    camera.orthographic_projection.scale *= EDITOR_ZOOM;
+    commands.spawn_bundle(camera);
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_editor(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<Camera>, With<EditorShape>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.insert_resource(ClearColor(Color::BLACK));
+}
+
+/// Put back the match settings from before the arena was tried out.
+fn finish_test_play(
+    mut commands: Commands,
+    test_play: Option<Res<TestPlay>>,
+    mut config: ResMut<MatchConfig>,
+) {
+    if let Some(test_play) = test_play {
+        *config = test_play.previous_config.clone();
+        commands.remove_resource::<TestPlay>();
+    }
+}
+
+fn test_play_ui(
+    mut commands: Commands,
+    mut ctx: ResMut<EguiContext>,
+) {
+    let window = egui::Window::new("Test Play")
+        .title_bar(false)
+        .auto_sized()
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -10.0]);
+    window.show(ctx.ctx_mut(), |ui| {
+        if ui.button("Back to Editor").clicked() {
+            commands.insert_resource(NextState(AppState::Editor));
+        }
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn editor_ui(
+    mut commands: Commands,
+    mut ctx: ResMut<EguiContext>,
+    mut state: ResMut<EditorState>,
+    mut config: ResMut<MatchConfig>,
+    mut assets: ResMut<Assets>,
+    server: Res<AssetServer>,
+    mut arenas: ResMut<bevy::asset::Assets<Arena>>,
+    modes: Res<GameModes>,
+    mut checked: Local<Option<(Arena, Result<(), String>)>>,
+) {
+    let window = egui::Window::new("Arena Editor")
+        .resizable(false)
+        .default_width(240.0)
+        .anchor(egui::Align2::LEFT_TOP, [10.0, 10.0]);
+    window.show(ctx.ctx_mut(), |ui| {
+        let state = &mut *state;
+
+        let mut loaded: Vec<_> = arenas.iter().map(|(_, arena)| arena).collect();
+        loaded.sort_by(|a, b| a.name.cmp(&b.name));
+        egui::ComboBox::from_label("Open")
+            .selected_text("Start from...")
+            .show_ui(ui, |ui| {
+                for arena in loaded {
+                    if ui.selectable_label(false, &arena.name).clicked() {
+                        state.open(arena.clone());
+                    }
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut state.arena.name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Background");
+            ui.color_edit_button_srgb(&mut state.arena.background);
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut state.tool, Tool::Select, "Select");
+            ui.radio_value(&mut state.tool, Tool::Wall, "Wall");
+            ui.radio_value(&mut state.tool, Tool::Obstacle, "Obstacle");
+            ui.radio_value(&mut state.tool, Tool::Goal, "Goal");
+        });
+        if state.tool == Tool::Goal {
+            egui::ComboBox::from_label("Goal side")
+                .selected_text(state.goal_side.label())
+                .show_ui(ui, |ui| {
+                    for &side in ALL_SIDES {
+                        ui.selectable_value(&mut state.goal_side, side, side.label());
+                    }
+                });
+        }
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut state.snap, "Snap to grid");
+            ui.add(egui::DragValue::new(&mut state.grid_size).clamp_range(1.0..=100.0));
+        });
+        ui.separator();
+
+        if let Some(selection) = state.selected {
+            selection_ui(ui, state, selection);
+            ui.separator();
+        }
+
+        // Only check the arena again when it changes, since finding out whether it's enclosed
+        // takes a while.
+        if checked.as_ref().map_or(true, |(arena, _)| *arena != state.arena) {
+            *checked = Some((state.arena.clone(), state.arena.validate()));
+        }
+        if let Some((_, Err(problem))) = &*checked {
+            ui.colored_label(egui::Color32::LIGHT_RED, problem);
+        }
+
+        if ui.button("Test Play").clicked() {
+            match state.arena.validate() {
+                Ok(()) => {
+                    commands.insert_resource(TestPlay { previous_config: config.clone() });
+                    config.four_player = state.arena.goals.len() == ALL_SIDES.len();
+                    config.solo &= !config.four_player;
+                    modes.pick_playable(&mut config);
+                    config.points_to_win = None;
+                    config.arena = state.arena.clone();
+                    state.status = None;
+                    commands.insert_resource(NextState(AppState::InGame));
+                }
+                Err(e) => state.status = Some(format!("Can't play: {}", e)),
+            }
+        }
+        if ALLOW_EXPORT {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut state.file_name);
+                ui.label(".arena.json");
+            });
+            if ui.button("Save").clicked() {
+                state.status = Some(save(state, &mut assets, &server, &mut arenas));
+            }
+        }
+        if let Some(status) = &state.status {
+            ui.label(status);
+        }
+        if ui.button("Main Menu").clicked() {
+            commands.insert_resource(NextState(AppState::MainMenu));
+        }
+
+        ui.separator();
+        ui.small("Drag to move, Shift+drag to resize, Delete to remove. Drag with the right mouse button to pan and scroll to zoom.");
+    });
+}
+
+/// Fields for the exact position and size of the selection.
+fn selection_ui(ui: &mut egui::Ui, state: &mut EditorState, selection: Selection) {
+    let drag_value = |ui: &mut egui::Ui, label: &str, value: &mut f32| {
+        ui.add(egui::DragValue::new(value).prefix(label));
+    };
+    if let Some(rect) = state.rect_mut(selection) {
+        let kind = if matches!(selection, Selection::Wall(_)) { "Wall" } else { "Obstacle" };
+        ui.strong(kind);
+        ui.horizontal(|ui| {
+            drag_value(ui, "x ", &mut rect.position[0]);
+            drag_value(ui, "y ", &mut rect.position[1]);
+        });
+        ui.horizontal(|ui| {
+            drag_value(ui, "width ", &mut rect.size[0]);
+            drag_value(ui, "height ", &mut rect.size[1]);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Rotation");
+            ui.add(egui::DragValue::new(&mut rect.rotation).suffix("°").clamp_range(-180.0..=180.0));
+        });
+    } else if let Selection::Goal(index) = selection {
+        if let Some((goal, lane)) = state.goal_mut(index) {
+            ui.strong(format!("{} goal", goal.side.label()));
+            ui.horizontal(|ui| {
+                drag_value(ui, "x ", &mut goal.position[0]);
+                drag_value(ui, "y ", &mut goal.position[1]);
+                drag_value(ui, "width ", &mut goal.width);
+            });
+            if let Some(lane) = lane {
+                ui.label("Paddle lane");
+                ui.horizontal(|ui| {
+                    drag_value(ui, "x ", &mut lane.position[0]);
+                    drag_value(ui, "y ", &mut lane.position[1]);
+                    drag_value(ui, "length ", &mut lane.length);
+                });
+            }
+        }
+    }
+    if ui.button("Delete").clicked() {
+        state.remove(selection);
+    }
+}
+
+/// Save the arena to `ARENAS_DIR`, and load it so it can be picked in the menu. Returns how it
+/// went.
+fn save(
+    state: &mut EditorState,
+    assets: &mut Assets,
+    server: &AssetServer,
+    arenas: &mut bevy::asset::Assets<Arena>,
+) -> String {
+    if let Err(e) = state.arena.validate() {
+        return format!("Can't save: {}", e);
+    }
+    if state.file_name.trim().is_empty() {
+        state.file_name = file_name_for(&state.arena.name);
+    }
+    let file_name = format!("{}.arena.json", state.file_name.trim());
+    let path = Path::new(ARENAS_DIR).join(&file_name);
+    if let Err(e) = state.arena.save(&path) {
+        return format!("Failed to save {}: {}", path.display(), e);
+    }
+
+    // The asset server won't load a file again once it has, so update the copy it has instead.
+    let handle = server.load(&format!("arenas/{}", file_name));
+    if let Some(arena) = arenas.get_mut(&handle) {
+        *arena = state.arena.clone();
+    }
+    if !assets.arenas.contains(&handle) {
+        assets.arenas.push(handle);
+    }
+    format!("Saved to {}", path.display())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn edit_with_mouse(
+    mut ctx: ResMut<EguiContext>,
+    windows: Res<Windows>,
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    mut motion_events: EventReader<MouseMotion>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+    mut state: ResMut<EditorState>,
+) {
+    let ctx = ctx.ctx_mut();
+    let (mut camera_transform, mut projection) = camera_q.single_mut();
+
+    if keys.just_pressed(KeyCode::Delete) && !ctx.wants_keyboard_input() {
+        if let Some(selection) = state.selected {
+            state.remove(selection);
+        }
+    }
+
+    // Leave the mouse to the editor window while it's over it, unless already dragging.
+    if ctx.is_pointer_over_area() && state.drag.is_none() {
+        motion_events.iter().for_each(drop);
+        wheel_events.iter().for_each(drop);
+        return;
+    }
+    let pan = buttons.pressed(MouseButton::Right) || buttons.pressed(MouseButton::Middle);
+    pan_and_zoom(&mut camera_transform, &mut projection, &windows, pan, &mut motion_events, &mut wheel_events);
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let window_size = Vec2::new(window.width(), window.height());
+    let units_per_pixel = projection.scale * 2.0 / window.height();
+    let point = camera_transform.translation.truncate() + (cursor - window_size / 2.0) * units_per_pixel;
+
+    if buttons.just_pressed(MouseButton::Left) {
+        let snapped = state.snap(point);
+        match state.tool {
+            Tool::Select => {
+                state.selected = state.hit(point);
+                state.drag = state.selected.and_then(|selection| {
+                    if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+                        Some(Drag::Resize)
+                    } else {
+                        state.position(selection).map(|position| Drag::Move { offset: position - point })
+                    }
+                });
+            }
+            Tool::Wall => {
+                state.arena.walls.push(ArenaRect::new(snapped, Vec2::ZERO));
+                state.selected = Some(Selection::Wall(state.arena.walls.len() - 1));
+                state.drag = Some(Drag::Draw { start: snapped });
+            }
+            Tool::Obstacle => {
+                state.arena.obstacles.push(ArenaRect::new(snapped, Vec2::ZERO));
+                state.selected = Some(Selection::Obstacle(state.arena.obstacles.len() - 1));
+                state.drag = Some(Drag::Draw { start: snapped });
+            }
+            Tool::Goal => {
+                let side = state.goal_side;
+                state.place_goal(side, snapped);
+            }
+        }
+    }
+
+    if let (Some(drag), Some(selection)) = (state.drag, state.selected) {
+        if buttons.pressed(MouseButton::Left) {
+            match drag {
+                Drag::Move { offset } => {
+                    let position = state.snap(point + offset);
+                    state.move_to(selection, position);
+                }
+                Drag::Resize => state.resize_to(selection, point),
+                Drag::Draw { start } => {
+                    let end = state.snap(point);
+                    if let Some(rect) = state.rect_mut(selection) {
+                        *rect = ArenaRect::new((start + end) / 2.0, (end - start).abs());
+                    }
+                }
+            }
+        } else {
+            // Don't keep walls or obstacles that were clicked rather than drawn.
+            let size = state.rect_mut(selection).map(|rect| rect.size());
+            if matches!(drag, Drag::Draw { .. }) && size.map_or(false, |size| size.min_element() <= 0.0) {
+                state.remove(selection);
+            }
+            state.drag = None;
+        }
+    }
+}
+
+/// Show the arena being edited, whenever it changes.
+#[allow(clippy::type_complexity)]
+fn draw_arena(
+    mut commands: Commands,
+    state: Res<EditorState>,
+    shape_q: Query<Entity, With<EditorShape>>,
+    mut drawn: Local<Option<(Arena, Option<Selection>, bool, f32)>>,
+) {
+    let current = (state.arena.clone(), state.selected, state.snap, state.grid_size);
+    if drawn.as_ref() == Some(&current) {
+        return;
+    }
+    *drawn = Some(current);
+
+    for entity in shape_q.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.insert_resource(ClearColor(state.arena.background()));
+
+    let mut spawn = |rect: ArenaRect, z: f32, color: Color| {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite { color, custom_size: Some(rect.size()), ..default() },
+                transform: Transform {
+                    translation: rect.position().extend(z),
+                    rotation: Quat::from_rotation_z(rect.rotation()),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(EditorShape);
+    };
+    let color = |selection: Selection, color: Color| {
+        if state.selected == Some(selection) { SELECTED_COLOR } else { color }
+    };
+
+    let arena = &state.arena;
+    if state.snap {
+        // Lines over everything in the arena, and a bit around it.
+        let mut extent = Vec2::splat(200.0);
+        let rects = arena.walls.iter().chain(&arena.obstacles).copied();
+        for rect in rects.chain(arena.goals.iter().map(ArenaGoal::mouth)) {
+            extent = extent.max(rect.position().abs() + rect.half_bounds() + 100.0);
+        }
+        let spacing = state.grid_size * (MIN_GRID_LINE_SPACING / state.grid_size).ceil().max(1.0);
+        let (columns, rows) = ((extent.x / spacing) as i32, (extent.y / spacing) as i32);
+        for column in -columns..=columns {
+            spawn(ArenaRect::new(Vec2::new(column as f32 * spacing, 0.0), Vec2::new(1.0, extent.y * 2.0)), -1.0, GRID_COLOR);
+        }
+        for row in -rows..=rows {
+            spawn(ArenaRect::new(Vec2::new(0.0, row as f32 * spacing), Vec2::new(extent.x * 2.0, 1.0)), -1.0, GRID_COLOR);
+        }
+    }
+
+    for (index, wall) in arena.walls.iter().enumerate() {
+        spawn(*wall, 0.0, color(Selection::Wall(index), Color::WHITE));
+    }
+    for (index, obstacle) in arena.obstacles.iter().enumerate() {
+        spawn(*obstacle, 0.1, color(Selection::Obstacle(index), OBSTACLE_COLOR));
+    }
+    for (index, goal) in arena.goals.iter().enumerate() {
+        let mouth = goal.mouth();
+        let side_color = match goal.side {
+            PlayerSide::Left => Color::rgba(0.3, 0.5, 1.0, 0.5),
+            PlayerSide::Right => Color::rgba(1.0, 0.3, 0.3, 0.5),
+            PlayerSide::Top => Color::rgba(0.3, 1.0, 0.4, 0.5),
+            PlayerSide::Bottom => Color::rgba(1.0, 0.8, 0.2, 0.5),
+        };
+        spawn(mouth, 0.2, color(Selection::Goal(index), side_color));
+    }
+    for lane in &arena.lanes {
+        let size = if lane.side.is_top_or_bottom() {
+            Vec2::new(lane.length, 2.0)
+        } else {
+            Vec2::new(2.0, lane.length)
+        };
+        spawn(ArenaRect::new(lane.position.into(), size), 0.3, LANE_COLOR);
+    }
+}
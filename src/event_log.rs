@@ -0,0 +1,331 @@
+use std::{
+    fs,
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    AppState,
+    game::{
+        BallBouncedEvent, BallHitWallEvent, BallMissedEvent, BallServedEvent, GameState, MatchConfig, MatchMode,
+        LifeLostEvent, MatchOverEvent, PlayerScoredEvent, PlayerSide,
+    },
+    power_up::{PowerUpClaimedEvent, PowerUpKind},
+    sim::SimulationAppExt,
+};
+
+/// Writes what happens in each match to a JSON lines file, one event per line.
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<EventLogSettings>()
+            .add_enter_system(AppState::InGame, start_log)
+            .add_exit_system(AppState::InGame, finish_log)
+            .add_simulation_system(record_events
+                .run_in_state(AppState::InGame)
+                .run_if_resource_exists::<MatchLog>()
+                .after("check_match_over"))
+            .add_system(toggle_event_log)
+            .add_system(start_or_stop_log.run_in_state(AppState::InGame).after(toggle_event_log));
+    }
+}
+
+/// Whether match events are logged, and where to.
+pub struct EventLogSettings {
+    pub enabled: bool,
+    /// Directory to create a log file in for each match.
+    pub directory: PathBuf,
+}
+
+impl Default for EventLogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: PathBuf::from("match-logs"),
+        }
+    }
+}
+
+/// A line in the log.
+#[derive(Debug, Clone, Serialize)]
+struct LogEntry {
+    /// Tick of the match the event happened in.
+    tick: u64,
+    /// When the event was recorded, in milliseconds since the Unix epoch.
+    timestamp: u64,
+    #[serde(flatten)]
+    event: LogEvent,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum LogEvent {
+    MatchStart {
+        seed: u64,
+        mode: MatchMode,
+        points_to_win: Option<u8>,
+        team_size: u8,
+        four_player: bool,
+        solo: bool,
+        game_mode: String,
+        arena: String,
+    },
+    Serve {
+        toward: PlayerSide,
+        speed: f32,
+        /// Degrees from straight at the receiving side, along its lane as in `PlayerSide::lane`.
+        angle: f32,
+    },
+    PaddleHit {
+        side: PlayerSide,
+        speed: f32,
+        /// Degrees from straight back out, along the paddle's lane as in `PlayerSide::lane`.
+        angle: f32,
+    },
+    WallHit {
+        speed: f32,
+    },
+    Goal {
+        scorer: PlayerSide,
+        left_score: u8,
+        right_score: u8,
+    },
+    LifeLost {
+        side: PlayerSide,
+        /// Lives the side has left.
+        lives: u8,
+    },
+    Miss {
+        side: PlayerSide,
+    },
+    PowerUp {
+        side: PlayerSide,
+        kind: PowerUpKind,
+    },
+    MatchEnd {
+        winner: PlayerSide,
+        /// Final score of each side, or lives left in a four-player match, in
+        /// `PlayerSide::index` order.
+        scores: Vec<u8>,
+    },
+}
+
+/// Log file of the match in progress. Each tick's events are written as soon as it's simulated.
+/// A network match may simulate ticks again, so the lines of those ticks are cut off the end of
+/// the file, to be written again.
+struct MatchLog {
+    path: PathBuf,
+    file: fs::File,
+    /// Ticks that have events logged, with the length of the file before their first line.
+    ticks: Vec<(u64, u64)>,
+    length: u64,
+}
+
+impl MatchLog {
+    fn create(directory: &Path) -> io::Result<Self> {
+        let path = directory.join(format!("match-{}.jsonl", timestamp()));
+        fs::create_dir_all(directory)?;
+        let file = fs::File::create(&path)?;
+        Ok(Self { path, file, ticks: Vec::new(), length: 0 })
+    }
+
+    /// Write the lines of a tick, replacing any already written for it or the ticks after.
+    fn write(&mut self, tick: u64, entries: &[LogEntry]) -> io::Result<()> {
+        let kept = self.ticks.partition_point(|&(logged, _)| logged < tick);
+        if let Some(&(_, length)) = self.ticks.get(kept) {
+            self.file.set_len(length)?;
+            self.file.seek(SeekFrom::Start(length))?;
+            self.ticks.truncate(kept);
+            self.length = length;
+        }
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut lines, entry)?;
+            writeln!(lines)?;
+        }
+        self.file.write_all(&lines)?;
+        self.file.flush()?;
+        self.ticks.push((tick, self.length));
+        self.length += lines.len() as u64;
+        Ok(())
+    }
+}
+
+/// Start logging to a new file, or turn logging back off if one can't be created.
+fn start(commands: &mut Commands, settings: &mut EventLogSettings) {
+    match MatchLog::create(&settings.directory) {
+        Ok(log) => {
+            info!("Logging match events to {}", log.path.display());
+            commands.insert_resource(log);
+        }
+        Err(e) => {
+            warn!("Failed to create a match log in {}: {}", settings.directory.display(), e);
+            settings.enabled = false;
+        }
+    }
+}
+
+/// Milliseconds since the Unix epoch.
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// Degrees a ball heading toward or away from `side` is turned along the side's lane.
+fn angle(velocity: Vec2, side: PlayerSide) -> f32 {
+    velocity.dot(side.lane()).atan2(velocity.dot(side.direction()).abs()).to_degrees()
+}
+
+fn toggle_event_log(
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<EventLogSettings>,
+) {
+    if keys.just_pressed(KeyCode::F2) {
+        settings.enabled = !settings.enabled;
+        info!("Match event log {}", if settings.enabled { "enabled" } else { "disabled" });
+    }
+}
+
+fn start_log(
+    mut commands: Commands,
+    mut settings: ResMut<EventLogSettings>,
+) {
+    if settings.enabled {
+        start(&mut commands, &mut settings);
+    }
+}
+
+/// Start a log when enabled mid-match, or stop logging when disabled.
+fn start_or_stop_log(
+    mut commands: Commands,
+    mut settings: ResMut<EventLogSettings>,
+    log: Option<Res<MatchLog>>,
+) {
+    match (settings.enabled, log) {
+        (true, None) => start(&mut commands, &mut settings),
+        (false, Some(log)) => {
+            info!("Saved match log to {}", log.path.display());
+            commands.remove_resource::<MatchLog>();
+        }
+        _ => {}
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_events(
+    mut commands: Commands,
+    mut log: ResMut<MatchLog>,
+    mut settings: ResMut<EventLogSettings>,
+    mut served: EventReader<BallServedEvent>,
+    mut bounced: EventReader<BallBouncedEvent>,
+    mut hit_wall: EventReader<BallHitWallEvent>,
+    mut player_scored: EventReader<PlayerScoredEvent>,
+    mut lives_lost: EventReader<LifeLostEvent>,
+    mut missed: EventReader<BallMissedEvent>,
+    mut power_ups: EventReader<PowerUpClaimedEvent>,
+    mut match_over: EventReader<MatchOverEvent>,
+    game_state: Res<GameState>,
+    config: Res<MatchConfig>,
+) {
+    // `check_match_over` has already counted this tick.
+    let tick = game_state.tick - 1;
+    let timestamp = timestamp();
+    let mut events = Vec::new();
+    if tick == 0 {
+        events.push(LogEvent::MatchStart {
+            seed: game_state.seed,
+            mode: config.mode,
+            points_to_win: config.points_to_win,
+            team_size: config.team_size(),
+            four_player: config.four_player,
+            solo: config.solo,
+            game_mode: config.game_mode.clone(),
+            arena: config.arena.name.clone(),
+        });
+    }
+    // In the order the systems sending them run.
+    events.extend(hit_wall.iter().map(|event| LogEvent::WallHit { speed: event.speed }));
+    events.extend(bounced.iter().map(|event| LogEvent::PaddleHit {
+        side: event.side,
+        speed: event.speed,
+        angle: event.angle,
+    }));
+    events.extend(power_ups.iter().map(|event| LogEvent::PowerUp { side: event.side, kind: event.kind }));
+
+    // Scores were already updated by `reset_round`, so work out what they were after each goal.
+    let goals: Vec<_> = player_scored.iter().map(|event| event.0).collect();
+    let mut scores = game_state.scores;
+    for scorer in &goals {
+        scores[scorer.index()] = scores[scorer.index()].saturating_sub(1);
+    }
+    for scorer in goals {
+        scores[scorer.index()] = scores[scorer.index()].saturating_add(1);
+        let (left_score, right_score) = (scores[PlayerSide::Left.index()], scores[PlayerSide::Right.index()]);
+        events.push(LogEvent::Goal { scorer, left_score, right_score });
+    }
+    // Likewise for the lives left after each one lost.
+    let losses: Vec<_> = lives_lost.iter().map(|event| event.0).collect();
+    let mut lives = game_state.scores;
+    for side in &losses {
+        lives[side.index()] += 1;
+    }
+    for side in losses {
+        lives[side.index()] -= 1;
+        events.push(LogEvent::LifeLost { side, lives: lives[side.index()] });
+    }
+    events.extend(missed.iter().map(|event| LogEvent::Miss { side: event.0 }));
+
+    events.extend(served.iter().map(|event| LogEvent::Serve {
+        toward: event.toward,
+        speed: event.velocity.length(),
+        angle: angle(event.velocity, event.toward),
+    }));
+    events.extend(match_over.iter().map(|event| LogEvent::MatchEnd {
+        winner: event.winner,
+        scores: config.sides().iter().map(|&side| game_state.score(side)).collect(),
+    }));
+
+    let entries: Vec<_> = events.into_iter().map(|event| LogEntry { tick, timestamp, event }).collect();
+    if let Err(e) = log.write(tick, &entries) {
+        warn!("Failed to write to match log {}, no longer logging: {}", log.path.display(), e);
+        settings.enabled = false;
+        commands.remove_resource::<MatchLog>();
+    }
+}
+
+fn finish_log(
+    mut commands: Commands,
+    log: Option<Res<MatchLog>>,
+) {
+    if let Some(log) = log {
+        info!("Saved match log to {}", log.path.display());
+        commands.remove_resource::<MatchLog>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn angle_along_lane() {
+        let turned = Vec2::new(1.0, 3_f32.sqrt());
+        assert!((angle(turned, PlayerSide::Right) - 60.0).abs() < 1e-3);
+        assert!((angle(-turned, PlayerSide::Left) + 60.0).abs() < 1e-3);
+        // Balls heading up and down are turned along the top and bottom lanes.
+        assert!((angle(turned, PlayerSide::Top) - 30.0).abs() < 1e-3);
+        assert!((angle(-turned, PlayerSide::Bottom) + 30.0).abs() < 1e-3);
+    }
+}
@@ -1,279 +1,1255 @@
+use std::ops::RangeInclusive;
+
 use bevy::prelude::*;
-use bevy::input::mouse::MouseMotion;
+use bevy::input::mouse::{MouseMotion, MouseWheel};
 use bevy::math::Mat2;
+use bevy::render::camera::{Camera2d, ScalingMode};
 use bevy_kira_audio::Audio;
-use heron::prelude::*;
 use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     WINDOW_SIZE, AppState,
+    ai::{self, AiAim, AiSettings},
+    arena::{Arena, ArenaRect},
     assets::Assets,
+    mode::{GameModes, GoalOutcome, classic::ClassicMode, multi_ball::MultiBallSettings},
+    net::RollbackAppExt,
+    profile::{ControlScheme, PlayerProfile},
+    replay::ReplayPlayback,
+    power_up::PowerUpSettings,
+    physics::{Collider, CollisionEvent, PhysicsPlugin, PhysicsSystem, Sensor, Velocity, reflect},
+    sim::{Position, PreviousPosition, SimulationAppExt, SimulationClock, TICK_DELTA, TICK_RATE},
 };
 
-const BALL_SIZE: f32 = 30.0;
-const BALL_START_SPEED: f32 = 400.0;
-const BALL_MAX_SPEED: f32 = 1000.0;
-const BALL_SPEED_INCREMENT: f32 = 20.0;
+pub const BALL_SIZE: f32 = 30.0;
+/// Fastest the ball can go, and so the fastest it can be served.
+pub const BALL_MAX_SPEED: f32 = 1000.0;
 
-const WALL_SIZE:(f32, f32) = (WINDOW_SIZE.0, 20.0);
-const GOAL_WIDTH: f32 = 40.0;
-const GOAL_DEPTH: f32 = 20.0;
+pub const OBSTACLE_COLOR: Color = Color::rgb(0.6, 0.6, 0.6);
 
-const PADDLE_SIZE: (f32, f32) = (20.0, 100.0);
-const PADDLE_GOAL_SEPARATION: f32 = 10.0;
+/// Thickness and length of a paddle.
+pub const PADDLE_SIZE: (f32, f32) = (20.0, 100.0);
 const PADDLE_SPEED: f32 = 300.0;
+/// How much further from its goal each extra paddle on a side stands.
+const TEAM_PADDLE_SPACING: f32 = 200.0;
+/// Most paddles a side can have.
+pub const MAX_TEAM_SIZE: u8 = 2;
 const MAX_BOUNCE_ANGLE: f32 = 45.0;
+/// How far in front of a paddle the ball waits to be served.
+const SERVE_DISTANCE: f32 = PADDLE_SIZE.0 / 2.0 + BALL_SIZE / 2.0 + 2.0;
+/// Seconds AI players wait before serving.
+const AI_SERVE_DELAY: u32 = 1;
+/// How high up the countdown before a serve is shown.
+const COUNTDOWN_HEIGHT: f32 = 80.0;
+/// Length and thickness of the arrow showing which way the ball is about to be served.
+const SERVE_ARROW_SIZE: (f32, f32) = (60.0, 6.0);
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_plugin(PhysicsPlugin::default())
+            .add_plugin(PhysicsPlugin)
             .add_event::<PlayerScoredEvent>()
+            .add_event::<LifeLostEvent>()
+            .add_event::<BallMissedEvent>()
+            .add_event::<BallBouncedEvent>()
+            .add_event::<BallHitWallEvent>()
+            .add_event::<BallServedEvent>()
+            .add_event::<MatchOverEvent>()
+            .init_resource::<MatchConfig>()
+            .init_resource::<GameModes>()
+            .add_rollback_resource::<GameState>()
+            .add_rollback_resource::<GameRng>()
+            .add_rollback_component::<Ball>()
+            .add_rollback_component::<LastHit>()
+            .add_rollback_component::<Spin>()
+            .add_rollback_component::<SpeedScale>()
+            .add_rollback_component::<Held>()
+            .add_rollback_component::<Serving>()
+            .add_rollback_component::<ServeCountdown>()
+            .add_rollback_component::<Wall>()
+            .add_rollback_component::<Goal>()
+            .add_rollback_component::<Paddle>()
+            .add_rollback_component::<Controller>()
+            .add_rollback_component::<PaddleInput>()
+            .add_rollback_component::<PaddleMotion>()
+            .add_rollback_component::<AiAim>()
             .add_enter_system(AppState::InGame, setup_game)
-            .add_system(paddle_control.run_in_state(AppState::InGame))
-            .add_system(ball_wall_bounce.run_in_state(AppState::InGame))
-            .add_system(ball_paddle_bounce.run_in_state(AppState::InGame))
-            .add_system(check_scored.run_in_state(AppState::InGame).label("check_scored"))
-            .add_system(reset_round.run_in_state(AppState::InGame).after("check_scored"));
+            .add_exit_system(AppState::InGame, despawn_game)
+            // Gameplay runs on the fixed-timestep simulation schedule, in this order.
+            .add_simulation_system(keyboard_input
+                .run_in_state(AppState::InGame)
+                .label("paddle_input"))
+            .add_simulation_system(ai::ai_input
+                .run_in_state(AppState::InGame)
+                .label("paddle_input"))
+            .add_simulation_system(paddle_control
+                .run_in_state(AppState::InGame)
+                .label("paddle_control")
+                .after("paddle_input")
+                .before(PhysicsSystem::Integrate))
+            .add_simulation_system(carry_held_balls
+                .run_in_state(AppState::InGame)
+                .after("paddle_control")
+                .before(PhysicsSystem::Integrate))
+            .add_simulation_system(serve_balls
+                .run_in_state(AppState::InGame)
+                .after("paddle_control")
+                .before(PhysicsSystem::Integrate))
+            .add_simulation_system(count_down_serves
+                .run_in_state(AppState::InGame)
+                .after("paddle_control")
+                .before(PhysicsSystem::Integrate))
+            .add_simulation_system(curve_balls
+                .run_in_state(AppState::InGame)
+                .after("paddle_control")
+                .before(PhysicsSystem::Integrate))
+            .add_simulation_system(ball_wall_bounce
+                .run_in_state(AppState::InGame)
+                .label("ball_wall_bounce")
+                .after(PhysicsSystem::DetectCollisions))
+            .add_simulation_system(ball_paddle_bounce
+                .run_in_state(AppState::InGame)
+                .label("ball_paddle_bounce")
+                .after("ball_wall_bounce"))
+            .add_simulation_system(check_scored
+                .run_in_state(AppState::InGame)
+                .label("check_scored")
+                .after("ball_paddle_bounce"))
+            .add_simulation_system(reset_round
+                .run_in_state(AppState::InGame)
+                .label("reset_round")
+                .after("check_scored"))
+            .add_simulation_system(check_match_over
+                .run_in_state(AppState::InGame)
+                .label("check_match_over")
+                .after("reset_round"))
+            .add_simulation_system(play_bounce_sound
+                .run_in_state(AppState::InGame)
+                .run_if_resource_exists::<Audio>()
+                .after("ball_paddle_bounce"))
+            .add_system(update_score_text.run_in_state(AppState::InGame))
+            .add_system(toggle_pause.run_in_state(AppState::InGame))
+            .add_system(show_countdown.run_in_state(AppState::InGame));
 
         if cfg!(debug_assertions) {
-            app.add_system(camera_control.run_in_state(AppState::InGame));
+            app.add_system(camera_control
+                .run_in_state(AppState::InGame)
+                .run_if_resource_exists::<Windows>());
+        }
+    }
+}
+
+/// Who controls each paddle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum MatchMode {
+    /// Two players sharing one keyboard.
+    Local,
+    /// The left player against a computer-controlled right paddle.
+    Ai,
+    /// Two computer-controlled paddles.
+    AiVsAi,
+    /// One player on this machine against one on another, started from the network lobby.
+    #[clap(skip)]
+    Network,
+}
+
+impl Default for MatchMode {
+    fn default() -> Self {
+        Self::Local
+    }
+}
+
+/// How paddles respond to their controls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum PaddleMovement {
+    /// Move at full speed the moment a key is pressed, and stop the moment it's let go.
+    Instant,
+    /// Speed up and slow down gradually, with a dash on the action key.
+    Inertia,
+}
+
+impl Default for PaddleMovement {
+    fn default() -> Self {
+        Self::Instant
+    }
+}
+
+impl PaddleMovement {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Instant => "Instant",
+            Self::Inertia => "Inertia",
+        }
+    }
+}
+
+/// Where the ball is served from after every point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum ServeStyle {
+    /// Launch the ball from the middle of the arena at a random angle straight away.
+    Center,
+    /// Rest the ball on the serving player's paddle until they launch it with the action key.
+    Paddle,
+}
+
+impl Default for ServeStyle {
+    fn default() -> Self {
+        Self::Center
+    }
+}
+
+impl ServeStyle {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Center => "From the middle",
+            Self::Paddle => "Off the paddle",
+        }
+    }
+}
+
+/// Changes that make a match easier or harder for one side, to even out players of different
+/// skill.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Handicap {
+    /// Length of the side's paddles, as a fraction of the usual.
+    pub paddle_length: f32,
+    /// Speed of the side's paddles, as a fraction of the usual.
+    pub paddle_speed: f32,
+    /// Width of the side's goal, as a fraction of the arena's. The rest of it is walled off.
+    pub goal_width: f32,
+    /// Points the side starts the match with, or extra lives in modes that count them.
+    pub starting_points: u8,
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Self {
+            paddle_length: 1.0,
+            paddle_speed: 1.0,
+            goal_width: 1.0,
+            starting_points: 0,
+        }
+    }
+}
+
+impl Handicap {
+    /// Fractions of the usual paddle length and speed a side can be given.
+    pub const PADDLE_RANGE: RangeInclusive<f32> = 0.5..=2.0;
+    /// Fractions of the arena's goal width a side can be given.
+    pub const GOAL_RANGE: RangeInclusive<f32> = 0.3..=1.0;
+    pub const MAX_STARTING_POINTS: u8 = 10;
+
+    /// Whether this changes nothing.
+    pub fn is_even(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// How this differs from an even match, like `paddle 80%, +2 points`.
+    pub fn describe(&self) -> String {
+        let mut changes = Vec::new();
+        let percent = |fraction: f32| (fraction * 100.0).round();
+        if self.paddle_length != 1.0 {
+            changes.push(format!("paddle {}%", percent(self.paddle_length)));
+        }
+        if self.paddle_speed != 1.0 {
+            changes.push(format!("speed {}%", percent(self.paddle_speed)));
+        }
+        if self.goal_width != 1.0 {
+            changes.push(format!("goal {}%", percent(self.goal_width)));
+        }
+        if self.starting_points > 0 {
+            changes.push(format!("+{} points", self.starting_points));
+        }
+        if changes.is_empty() {
+            "even".into()
+        } else {
+            changes.join(", ")
+        }
+    }
+}
+
+/// Tunables for `PaddleMovement::Inertia`, in pixels and seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InertiaSettings {
+    /// How quickly paddles speed up while their keys are held.
+    pub acceleration: f32,
+    /// How quickly paddles slow down once their keys are let go.
+    pub friction: f32,
+    pub max_speed: f32,
+    /// Speed of a dash, whatever the paddle was going at before.
+    pub dash_speed: f32,
+    /// How long a dash lasts.
+    pub dash_time: f32,
+    /// How long after a dash starts before the paddle can dash again.
+    pub dash_cooldown: f32,
+}
+
+impl Default for InertiaSettings {
+    fn default() -> Self {
+        Self {
+            acceleration: 2000.0,
+            friction: 1600.0,
+            max_speed: PADDLE_SPEED,
+            dash_speed: 800.0,
+            dash_time: 0.12,
+            dash_cooldown: 1.0,
+        }
+    }
+}
+
+/// Settings for the next match. Picked in the main menu or on the command line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchConfig {
+    pub mode: MatchMode,
+    /// How the left and right paddles play when computer-controlled.
+    pub ai: [AiSettings; 2],
+    /// Seed for everything random in the match. Picked at random when not set.
+    pub seed: Option<u64>,
+    /// Score needed to win the match. The match goes on forever when not set.
+    pub points_to_win: Option<u8>,
+    /// Number of paddles on each side, from 1 to `MAX_TEAM_SIZE`.
+    #[serde(default = "default_team_size")]
+    pub team_size: u8,
+    /// Play with a goal and a paddle on every edge instead of just the left and right. Only one
+    /// paddle per side.
+    #[serde(default)]
+    pub four_player: bool,
+    /// Play alone, with a paddle on the left and a wall where the right goal would be.
+    #[serde(default)]
+    pub solo: bool,
+    /// ID of the `GameMode` whose rules the match is played by.
+    #[serde(default = "default_game_mode")]
+    pub game_mode: String,
+    /// Goals each side can let in before it's out, in modes where sides have lives.
+    #[serde(default = "default_lives")]
+    pub lives: u8,
+    /// Seconds of play in modes with a clock.
+    #[serde(default = "default_time_limit")]
+    pub time_limit: u32,
+    /// When extra balls come into play, in modes with more than one.
+    #[serde(default)]
+    pub multi_ball: MultiBallSettings,
+    /// When power-ups appear and what they do.
+    #[serde(default)]
+    pub power_ups: PowerUpSettings,
+    /// Speed the ball is served at.
+    #[serde(default = "default_ball_speed")]
+    pub ball_speed: f32,
+    /// Speed the ball gains every time a paddle returns it, up to `BALL_MAX_SPEED`.
+    #[serde(default = "default_speed_increment")]
+    pub speed_increment: f32,
+    /// Degrees a second the ball curves by after being hit by a paddle moving at full speed. No
+    /// spin when 0.
+    #[serde(default)]
+    pub spin: f32,
+    /// How much of its spin the ball loses every second, from 0 to 1.
+    #[serde(default = "default_spin_decay")]
+    pub spin_decay: f32,
+    /// How paddles respond to their controls.
+    #[serde(default)]
+    pub paddle_movement: PaddleMovement,
+    #[serde(default)]
+    pub inertia: InertiaSettings,
+    /// Where the ball is served from after every point.
+    #[serde(default)]
+    pub serve: ServeStyle,
+    /// Seconds players get to serve off their paddle before the ball goes anyway.
+    #[serde(default = "default_serve_timeout")]
+    pub serve_timeout: u32,
+    /// Seconds the ball waits in the middle before it's served from there. Served straight away
+    /// when 0.
+    #[serde(default)]
+    pub countdown: u32,
+    /// Whether paddles go back to the middle of their lanes after every point.
+    #[serde(default)]
+    pub reset_paddles: bool,
+    /// Handicaps of the left and the right, indexed by `PlayerSide::index`.
+    #[serde(default)]
+    pub handicaps: [Handicap; 2],
+    /// Where the match is played. Has to have goals for exactly the sides being played.
+    #[serde(default)]
+    pub arena: Arena,
+    /// Profiles of each player, when picked, in the order given by `player_index`.
+    #[serde(default)]
+    pub players: Vec<Option<PlayerProfile>>,
+}
+
+fn default_team_size() -> u8 {
+    1
+}
+
+fn default_game_mode() -> String {
+    ClassicMode::ID.into()
+}
+
+fn default_lives() -> u8 {
+    3
+}
+
+fn default_time_limit() -> u32 {
+    120
+}
+
+fn default_ball_speed() -> f32 {
+    400.0
+}
+
+fn default_speed_increment() -> f32 {
+    20.0
+}
+
+fn default_spin_decay() -> f32 {
+    0.5
+}
+
+fn default_serve_timeout() -> u32 {
+    5
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            mode: MatchMode::default(),
+            ai: Default::default(),
+            seed: None,
+            points_to_win: None,
+            team_size: default_team_size(),
+            four_player: false,
+            solo: false,
+            game_mode: default_game_mode(),
+            lives: default_lives(),
+            time_limit: default_time_limit(),
+            multi_ball: MultiBallSettings::default(),
+            power_ups: PowerUpSettings::default(),
+            ball_speed: default_ball_speed(),
+            speed_increment: default_speed_increment(),
+            spin: 0.0,
+            spin_decay: default_spin_decay(),
+            paddle_movement: PaddleMovement::default(),
+            inertia: InertiaSettings::default(),
+            serve: ServeStyle::default(),
+            serve_timeout: default_serve_timeout(),
+            countdown: 0,
+            reset_paddles: false,
+            handicaps: Default::default(),
+            arena: Arena::default(),
+            players: Vec::new(),
+        }
+    }
+}
+
+/// Index of the player controlling a paddle. Players alternate between the left and the right,
+/// and the paddles closest to the goals come first. So in doubles, players 1 and 3 play on the
+/// left. In a four-player match, players 3 and 4 play on the top and the bottom.
+pub fn player_index(side: PlayerSide, slot: u8) -> usize {
+    slot as usize * SIDES.len() + side.index()
+}
+
+impl MatchConfig {
+    /// Sides with a goal and paddles.
+    pub fn sides(&self) -> &'static [PlayerSide] {
+        if self.four_player {
+            ALL_SIDES
+        } else if self.solo {
+            SOLO_SIDES
+        } else {
+            SIDES
+        }
+    }
+
+    /// Sides the arena needs goals for. Solo matches wall off the right goal, so they're played
+    /// in the same arenas as classic ones.
+    pub fn arena_sides(&self) -> &'static [PlayerSide] {
+        if self.four_player {
+            ALL_SIDES
+        } else {
+            SIDES
+        }
+    }
+
+    /// Paddles per side. Four-player and solo matches have one each, whatever `team_size` says.
+    pub fn team_size(&self) -> u8 {
+        if self.four_player || self.solo {
+            1
+        } else {
+            self.team_size
+        }
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.team_size() as usize * self.sides().len()
+    }
+
+    /// Side a player is on.
+    pub fn player_side(&self, player: usize) -> PlayerSide {
+        let sides = self.sides();
+        sides[player % sides.len()]
+    }
+
+    /// Whether the paddles on a side are computer-controlled. Against the computer, only the
+    /// left side is played from the keyboard.
+    pub fn is_cpu(&self, side: PlayerSide) -> bool {
+        match self.mode {
+            MatchMode::AiVsAi => true,
+            MatchMode::Ai => side != PlayerSide::Left,
+            MatchMode::Local | MatchMode::Network => false,
+        }
+    }
+
+    /// How the paddles on a side play when computer-controlled. The top and bottom play like
+    /// the right.
+    pub fn ai_settings(&self, side: PlayerSide) -> AiSettings {
+        match side {
+            PlayerSide::Left => self.ai[0],
+            PlayerSide::Right | PlayerSide::Top | PlayerSide::Bottom => self.ai[1],
+        }
+    }
+
+    /// Profile of a player, unless their paddle is computer-controlled.
+    pub fn profile(&self, player: usize) -> Option<&PlayerProfile> {
+        if self.is_cpu(self.player_side(player)) {
+            return None;
+        }
+        self.players.get(player).and_then(Option::as_ref)
+    }
+
+    /// Name shown for a player.
+    pub fn player_name(&self, player: usize) -> String {
+        if let Some(profile) = self.profile(player) {
+            return profile.name.clone();
+        }
+        let side = self.player_side(player);
+        if self.is_cpu(side) {
+            // Number the computer players, unless there's only one.
+            let is_cpu = |player| self.is_cpu(self.player_side(player));
+            if (0..self.player_count()).filter(|&player| is_cpu(player)).count() == 1 {
+                return "CPU".into();
+            }
+            return format!("CPU {}", (0..player).filter(|&player| is_cpu(player)).count() + 1);
+        }
+        match (self.mode, side) {
+            (MatchMode::Network, PlayerSide::Left) => "Host".into(),
+            (MatchMode::Network, _) => "Guest".into(),
+            _ => format!("Player {}", player + 1),
         }
     }
+
+    /// Names of everyone playing on a side.
+    pub fn team_name(&self, side: PlayerSide) -> String {
+        let names: Vec<_> = (0..self.team_size())
+            .map(|slot| self.player_name(player_index(side, slot)))
+            .collect();
+        names.join(" & ")
+    }
+
+    /// Control scheme a player moves their paddle with.
+    pub fn controls(&self, player: usize) -> ControlScheme {
+        self.profile(player).map_or_else(|| ControlScheme::default_for(player), |profile| profile.controls)
+    }
+
+    /// Keys a player moves their paddle with, toward positive and negative coordinates along
+    /// its lane: up and down on the left and right, right and left on the top and bottom.
+    pub fn keys(&self, player: usize) -> (KeyCode, KeyCode) {
+        let controls = self.controls(player);
+        if self.player_side(player).is_top_or_bottom() {
+            controls.sideways_keys()
+        } else {
+            controls.keys()
+        }
+    }
+
+    /// Handicap of a side. Only the left and the right can have one.
+    pub fn handicap(&self, side: PlayerSide) -> Handicap {
+        self.handicaps.get(side.index()).copied().unwrap_or_default()
+    }
+
+    /// Key for a player's paddle's action.
+    pub fn action_key(&self, player: usize) -> KeyCode {
+        self.controls(player).action_key()
+    }
+
+    /// Colour of a player's paddle.
+    pub fn color(&self, player: usize) -> Color {
+        self.profile(player).map_or(Color::WHITE, PlayerProfile::color)
+    }
+}
+
+/// Deterministic random number generator for the simulation, so that a match can be reproduced
+/// from its seed. Only the seed is stored, which keeps it cheap to copy.
+#[derive(Debug, Clone, Copy, Hash)]
+pub struct GameRng(u64);
+
+impl GameRng {
+    pub fn with_seed(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> fastrand::Rng {
+        let rng = fastrand::Rng::with_seed(self.0);
+        self.0 = rng.u64(..);
+        rng
+    }
+
+    pub fn f32(&mut self) -> f32 {
+        self.next().f32()
+    }
+
+    pub fn usize(&mut self, range: std::ops::Range<usize>) -> usize {
+        self.next().usize(range)
+    }
+}
+
+/// Where a paddle's input comes from.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub enum Controller {
+    /// `up` and `down` move paddles on the top and bottom right and left.
+    Keyboard {
+        up: KeyCode,
+        down: KeyCode,
+        action: KeyCode,
+    },
+    Ai(AiSettings),
+    /// Driven by the replay being played back.
+    Replay,
+    /// Driven by the network session, from the local keyboard or the other player's inputs.
+    Network,
+}
+
+/// What a paddle's controller wants it to do this tick.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PaddleInput {
+    /// 1 to move up, -1 to move down, 0 to stay put. Paddles on the top and bottom move right
+    /// and left instead.
+    pub movement: i8,
+    /// Whether the action key is held.
+    #[serde(default)]
+    pub action: bool,
 }
 
+/// How a paddle is moving along its lane.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct PaddleMotion {
+    /// Speed along the lane, toward positive coordinates.
+    pub velocity: f32,
+    /// Ticks left of the dash in progress.
+    pub dash_ticks: u32,
+    /// Ticks until the paddle can dash again.
+    pub cooldown_ticks: u32,
+    /// Whether the action key was held last tick, so that holding it only dashes once.
+    action_held: bool,
+}
+
+impl PaddleMotion {
+    /// Speed up, slow down or dash with `PaddleMovement::Inertia`, returning the new velocity.
+    /// Every speed is scaled by `scale`.
+    fn accelerate(&mut self, movement: i8, action: bool, settings: &InertiaSettings, scale: f32) -> f32 {
+        let pressed = action && !self.action_held;
+        self.action_held = action;
+        self.cooldown_ticks = self.cooldown_ticks.saturating_sub(1);
+        if pressed && movement != 0 && self.cooldown_ticks == 0 {
+            self.dash_ticks = (settings.dash_time * TICK_RATE as f32).round() as u32;
+            self.cooldown_ticks = (settings.dash_cooldown * TICK_RATE as f32).round() as u32;
+            self.velocity = settings.dash_speed * scale * movement as f32;
+        }
+
+        if self.dash_ticks > 0 {
+            self.dash_ticks -= 1;
+        } else if movement != 0 {
+            let velocity = self.velocity + settings.acceleration * scale * TICK_DELTA * movement as f32;
+            let max_speed = settings.max_speed * scale;
+            self.velocity = velocity.clamp(-max_speed, max_speed);
+        } else {
+            let slowed = (self.velocity.abs() - settings.friction * scale * TICK_DELTA).max(0.0);
+            self.velocity = slowed.copysign(self.velocity);
+        }
+        self.velocity
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Ball;
+
+/// How fast a ball's path curves, in radians a second anticlockwise.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Spin(pub f32);
+
+/// How much a ball's speed is currently scaled by power-ups. Served balls start back at 1.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct SpeedScale(pub f32);
+
+impl Default for SpeedScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Ball resting against a paddle and carried along with it, until it's let go with `velocity`.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Held {
+    pub paddle: Entity,
+    /// Where the ball is relative to the paddle.
+    pub offset: Vec2,
+    pub velocity: Vec2,
+    /// Ticks until the ball is let go.
+    pub ticks_left: u32,
+}
+
+/// Ball waiting in front of a paddle for its player to serve it.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Serving {
+    pub paddle: Entity,
+    /// Ticks the ball has been waiting for.
+    pub ticks: u32,
+    /// Whether the action key was held last tick, so that one held from before has to be
+    /// pressed again.
+    action_held: bool,
+}
+
+impl Serving {
+    fn new(paddle: Entity) -> Self {
+        Self { paddle, ticks: 0, action_held: true }
+    }
+}
+
+/// Ball waiting in the middle of the arena to be served with `velocity`.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ServeCountdown {
+    pub ticks_left: u32,
+    pub velocity: Vec2,
+}
+
+impl ServeCountdown {
+    pub(crate) fn new(seconds: u32, velocity: Vec2) -> Self {
+        Self { ticks_left: seconds * TICK_RATE, velocity }
+    }
+
+    /// Whole seconds left, counting the one in progress.
+    pub fn seconds_left(&self) -> u32 {
+        (self.ticks_left + TICK_RATE - 1) / TICK_RATE
+    }
+}
+
+/// Shows the seconds left before a serve.
+#[derive(Component)]
+struct CountdownText;
+
+/// Points the way a ball is about to be served, from the ball.
 #[derive(Component)]
-struct Ball;
+struct ServeArrow;
+
+/// Side whose paddle last returned a ball, if any has since it was served.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct LastHit(pub Option<PlayerSide>);
 
 #[derive(Bundle)]
-struct BallBundle {
+pub struct BallBundle {
     ball: Ball,
+    last_hit: LastHit,
+    spin: Spin,
+    speed_scale: SpeedScale,
     #[bundle]
     sprite_bundle: SpriteBundle,
-    collision_shape: CollisionShape,
-    rigid_body: RigidBody,
-    rotation_constraints: RotationConstraints,
+    position: Position,
+    previous_position: PreviousPosition,
+    collider: Collider,
     velocity: Velocity,
 }
 
 impl BallBundle {
-    fn new(translation: Vec3, velocity: Vec3) -> Self {
+    fn new(translation: Vec2, velocity: Vec2) -> Self {
         let sprite_bundle = SpriteBundle {
             sprite: Sprite {
                 color: Color::WHITE,
                 custom_size: Some(Vec2::new(BALL_SIZE, BALL_SIZE)),
                 ..default()
             },
-            transform: Transform::from_translation(translation),
+            transform: Transform::from_translation(translation.extend(0.0)),
             ..default()
         };
-        let collision_shape = CollisionShape::Cuboid {
-            half_extends: Vec3::new(BALL_SIZE / 2.0, BALL_SIZE / 2.0, 0.0),
-            border_radius: None,
-        };
         Self {
             ball: Ball,
+            last_hit: LastHit::default(),
+            spin: Spin::default(),
+            speed_scale: SpeedScale::default(),
             sprite_bundle,
-            collision_shape,
-            rigid_body: RigidBody::KinematicVelocityBased,
-            rotation_constraints: RotationConstraints::lock(),
+            position: Position(translation),
+            previous_position: PreviousPosition(translation),
+            collider: Collider::cuboid(Vec2::new(BALL_SIZE, BALL_SIZE)),
             velocity: Velocity::from_linear(velocity),
         }
     }
 
-    fn from_side(side: PlayerSide) -> Self {
-        let angle = (fastrand::f32() * MAX_BOUNCE_ANGLE * 2.0) - MAX_BOUNCE_ANGLE;
-        let direction = Vec2::X * side.multiplier() as f32;
-        let direction = Mat2::from_angle(angle.to_radians()).mul_vec2(direction);
-        Self::new(Vec3::ZERO, direction.extend(0.0) * BALL_START_SPEED)
+    /// Ball in the middle of the arena, served toward a side.
+    pub fn from_side(side: PlayerSide, speed: f32, rng: &mut GameRng) -> Self {
+        Self::new(Vec2::ZERO, serve_velocity(side, speed, rng))
+    }
+
+    /// The same ball, somewhere else.
+    pub fn at(mut self, translation: Vec2) -> Self {
+        self.sprite_bundle.transform.translation = translation.extend(0.0);
+        self.position.0 = translation;
+        self.previous_position.0 = translation;
+        self
     }
 }
 
-#[derive(Component)]
+/// Side whose front paddle serves toward `toward` with `ServeStyle::Paddle`: the one across from
+/// it, or its own in matches without anyone across, like practice.
+fn server_side(toward: PlayerSide, can_serve: impl Fn(PlayerSide) -> bool) -> Option<PlayerSide> {
+    [toward.opposite(), toward].into_iter().find(|&side| can_serve(side))
+}
+
+/// Pick a random starting velocity for a ball served toward the given side.
+fn serve_velocity(side: PlayerSide, speed: f32, rng: &mut GameRng) -> Vec2 {
+    let angle = (rng.f32() * MAX_BOUNCE_ANGLE * 2.0) - MAX_BOUNCE_ANGLE;
+    let direction = Mat2::from_angle(angle.to_radians()).mul_vec2(side.direction());
+    direction * speed.min(BALL_MAX_SPEED)
+}
+
+#[derive(Component, Debug, Clone, Copy)]
 struct Wall;
 
 #[derive(Bundle)]
-struct WallBundle {
+pub struct WallBundle {
     wall: Wall,
     #[bundle]
     sprite_bundle: SpriteBundle,
-    collision_shape: CollisionShape,
-    rigid_body: RigidBody,
-    rotation_constraints: RotationConstraints,
+    position: Position,
+    collider: Collider,
 }
 
 impl WallBundle {
-    fn new(translation: Vec3) -> Self {
+    pub fn new(rect: &ArenaRect, color: Color) -> Self {
         let sprite_bundle = SpriteBundle {
             sprite: Sprite {
-                color: Color::WHITE,
-                custom_size: Some(Vec2::new(WALL_SIZE.0, WALL_SIZE.1)),
+                color,
+                custom_size: Some(rect.size()),
+                ..default()
+            },
+            transform: Transform {
+                translation: rect.position().extend(0.0),
+                rotation: Quat::from_rotation_z(rect.rotation()),
                 ..default()
             },
-            transform: Transform::from_translation(translation),
             ..default()
         };
-        let collision_shape = CollisionShape::Cuboid {
-            half_extends: Vec3::new(WALL_SIZE.0 / 2.0, WALL_SIZE.1 / 2.0, 0.0),
-            border_radius: None,
-        };
         Self {
             wall: Wall,
             sprite_bundle,
-            collision_shape,
-            rigid_body: RigidBody::Static,
-            rotation_constraints: RotationConstraints::lock(),
+            position: Position(rect.position()),
+            collider: Collider::cuboid(rect.size()).with_rotation(rect.rotation()),
         }
     }
 }
 
+/// Text shown over the arena while the match is paused.
 #[derive(Component)]
-struct Goal;
+struct PauseText;
 
-#[derive(Component)]
-struct Paddle;
+/// Sensor behind a side's paddles. The ball going in costs that side a point or a life.
+#[derive(Component, Clone, Copy)]
+struct Goal {
+    side: PlayerSide,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Paddle {
+    pub side: PlayerSide,
+    /// Position among the paddles on its side, counting out from the goal.
+    pub slot: u8,
+    /// Where the middle of the paddle's lane is, along the lane.
+    pub lane_middle: f32,
+    /// Length of the paddle's lane, which it stays entirely within.
+    pub lane_length: f32,
+    /// Length of the paddle itself, along its lane.
+    pub length: f32,
+    /// Length of the paddle without any power-ups.
+    pub base_length: f32,
+    /// Fastest the paddle moves along its lane with `PaddleMovement::Instant`.
+    pub speed: f32,
+    /// Whether the paddle moves the opposite way to its input.
+    pub reversed: bool,
+}
+
+impl Paddle {
+    /// Furthest the paddle can move from the middle of its lane.
+    pub fn reach(&self) -> f32 {
+        (self.lane_length / 2.0) - (self.length / 2.0)
+    }
+
+    /// Size of the paddle. Paddles on the top and bottom lie on their side.
+    pub fn size(&self) -> Vec2 {
+        if self.side.is_top_or_bottom() {
+            Vec2::new(self.length, PADDLE_SIZE.0)
+        } else {
+            Vec2::new(PADDLE_SIZE.0, self.length)
+        }
+    }
+}
 
 #[derive(Bundle)]
 struct PaddleBundle {
     paddle: Paddle,
     #[bundle]
     sprite_bundle: SpriteBundle,
-    collision_shape: CollisionShape,
-    rigid_body: RigidBody,
-    rotation_constraints: RotationConstraints,
+    position: Position,
+    previous_position: PreviousPosition,
+    collider: Collider,
+    controller: Controller,
+    input: PaddleInput,
+    motion: PaddleMotion,
+    ai_aim: AiAim,
 }
 
 impl PaddleBundle {
-    fn new(translation: Vec3) -> Self {
+    fn new(translation: Vec2, paddle: Paddle, controller: Controller, color: Color) -> Self {
+        let size = paddle.size();
         let sprite_bundle = SpriteBundle {
             sprite: Sprite {
-                color: Color::WHITE,
-                custom_size: Some(Vec2::new(PADDLE_SIZE.0, PADDLE_SIZE.1)),
+                color,
+                custom_size: Some(size),
                 ..default()
             },
-            transform: Transform::from_translation(translation),
+            transform: Transform::from_translation(translation.extend(0.0)),
             ..default()
         };
-        let collision_shape = CollisionShape::Cuboid {
-            half_extends: Vec3::new(PADDLE_SIZE.0 / 2.0, PADDLE_SIZE.1 / 2.0, 0.0),
-            border_radius: None,
-        };
         Self {
-            paddle: Paddle,
+            paddle,
             sprite_bundle,
-            collision_shape,
-            rigid_body: RigidBody::KinematicPositionBased,
-            rotation_constraints: RotationConstraints::lock(),
+            position: Position(translation),
+            previous_position: PreviousPosition(translation),
+            collider: Collider::cuboid(size),
+            controller,
+            input: PaddleInput::default(),
+            motion: PaddleMotion::default(),
+            ai_aim: AiAim::default(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum PlayerSide {
+/// Edge of the arena a player defends. Only the left and right are played on, except in
+/// four-player matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PlayerSide {
     Left,
     Right,
+    Top,
+    Bottom,
 }
 
+/// Sides played on in a classic match.
 const SIDES: &[PlayerSide] = &[PlayerSide::Left, PlayerSide::Right];
+/// Side played on in a solo match.
+const SOLO_SIDES: &[PlayerSide] = &[PlayerSide::Left];
+/// Sides played on in a four-player match, in the order players are assigned to them and serves
+/// go around in.
+pub const ALL_SIDES: &[PlayerSide] = &[PlayerSide::Left, PlayerSide::Right, PlayerSide::Top, PlayerSide::Bottom];
 
 impl PlayerSide {
-    fn random() -> Self {
-        SIDES[fastrand::usize(..SIDES.len())]
+    pub fn random(sides: &[Self], rng: &mut GameRng) -> Self {
+        sides[rng.usize(0..sides.len())]
     }
 
-    fn next(&self) -> Self {
+    pub fn opposite(&self) -> Self {
         match self {
             Self::Left => Self::Right,
             Self::Right => Self::Left,
+            Self::Top => Self::Bottom,
+            Self::Bottom => Self::Top,
         }
     }
 
-    fn multiplier(&self) -> i8 {
+    /// 0 for the left, 1 for the right, 2 for the top and 3 for the bottom, for indexing per-side
+    /// arrays.
+    pub fn index(&self) -> usize {
         match self {
-            Self::Left => -1,
+            Self::Left => 0,
             Self::Right => 1,
+            Self::Top => 2,
+            Self::Bottom => 3,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Top => "Top",
+            Self::Bottom => "Bottom",
+        }
+    }
+
+    pub fn is_top_or_bottom(&self) -> bool {
+        matches!(self, Self::Top | Self::Bottom)
+    }
+
+    /// Unit vector pointing from the middle of the arena toward this side.
+    pub fn direction(&self) -> Vec2 {
+        match self {
+            Self::Left => Vec2::new(-1.0, 0.0),
+            Self::Right => Vec2::new(1.0, 0.0),
+            Self::Top => Vec2::new(0.0, 1.0),
+            Self::Bottom => Vec2::new(0.0, -1.0),
+        }
+    }
+
+    /// Unit vector along the lane this side's paddles move in.
+    pub fn lane(&self) -> Vec2 {
+        if self.is_top_or_bottom() {
+            Vec2::X
+        } else {
+            Vec2::Y
         }
     }
 }
 
-struct PlayerScoredEvent(PlayerSide);
+/// Sent when a side scores a point.
+pub struct PlayerScoredEvent(pub PlayerSide);
+
+/// Sent when the ball goes into a side's goal and costs it a life.
+pub struct LifeLostEvent(pub PlayerSide);
+
+/// Sent when the ball goes into a side's goal without anyone scoring, like in practice.
+pub struct BallMissedEvent(pub PlayerSide);
+
+/// Sent when the ball bounces off a paddle.
+pub struct BallBouncedEvent {
+    pub ball: Entity,
+    pub paddle: Entity,
+    /// Side the paddle plays on.
+    pub side: PlayerSide,
+    /// Speed of the ball after the bounce.
+    pub speed: f32,
+    /// Degrees the ball left the paddle at, from straight back out, toward the positive end of
+    /// the paddle's lane.
+    pub angle: f32,
+}
+
+/// Sent when the ball bounces off a wall.
+pub struct BallHitWallEvent {
+    pub speed: f32,
+}
+
+/// Sent when the ball is served, at the start of the match and after every point.
+pub struct BallServedEvent {
+    /// Side the ball is heading toward.
+    pub toward: PlayerSide,
+    pub velocity: Vec2,
+}
+
+/// Sent when the game mode declares a winner.
+pub struct MatchOverEvent {
+    pub winner: PlayerSide,
+}
 
-struct GameState {
-    left_paddle: Entity,
-    right_paddle: Entity,
-    left_goal: Entity,
-    right_goal: Entity,
+#[derive(Clone)]
+pub struct GameState {
+    /// Seed the match's `GameRng` started from.
+    pub seed: u64,
+    /// Number of ticks simulated since the match started.
+    pub tick: u64,
+    /// Number of those ticks the ball was in play for.
+    pub play_ticks: u64,
+    /// Every paddle, in the order given by `player_index`.
+    pub paddles: Vec<Entity>,
+    /// Side the ball in play was served toward.
+    pub serve: PlayerSide,
     next_serve: PlayerSide,
-    left_score: u8,
-    right_score: u8,
-    left_score_text: Entity,
-    right_score_text: Entity,
+    /// Points of each side, or lives left in modes that count them, indexed by
+    /// `PlayerSide::index`.
+    pub scores: [u8; 4],
+    /// Last ball in play to have gone in this tick, to be served again.
+    serve_ball: Option<Entity>,
+    score_texts: Vec<(PlayerSide, Entity)>,
+}
+
+impl GameState {
+    /// State at the start of a match, with the first ball served toward `serve`.
+    pub fn new(seed: u64, paddles: Vec<Entity>, serve: PlayerSide) -> Self {
+        Self {
+            seed,
+            tick: 0,
+            play_ticks: 0,
+            paddles,
+            serve,
+            next_serve: serve,
+            scores: [0; 4],
+            serve_ball: None,
+            score_texts: Vec::new(),
+        }
+    }
+
+    pub fn score(&self, side: PlayerSide) -> u8 {
+        self.scores[side.index()]
+    }
+
+    /// Add to a side's score, stopping at the most a score can hold in matches with no points to
+    /// win.
+    pub fn add_points(&mut self, side: PlayerSide, points: u8) {
+        let score = &mut self.scores[side.index()];
+        *score = score.saturating_add(points);
+    }
+}
+
+/// Furthest the center of the ball can go from the middle of a paddle's lane, when there are
+/// walls at both ends of the lane.
+pub fn ball_limit(paddle: &Paddle) -> f32 {
+    paddle.reach() + (paddle.length / 2.0) - (BALL_SIZE / 2.0)
+}
+
+/// Where a side's score goes, in the side's half of the arena.
+pub fn score_position(side: PlayerSide, config: &MatchConfig) -> Vec2 {
+    if config.four_player {
+        side.direction() * 200.0 + Vec2::new(0.0, 30.0)
+    } else {
+        Vec2::new(side.direction().x * 200.0, 200.0)
+    }
+}
+
+/// 2D camera that always shows the full height of the arena, whatever the window size.
+pub fn arena_camera() -> OrthographicCameraBundle<Camera2d> {
+    let mut camera = OrthographicCameraBundle::new_2d();
+    camera.orthographic_projection.scaling_mode = ScalingMode::FixedVertical;
+    camera.orthographic_projection.scale = WINDOW_SIZE.1 / 2.0;
+    camera
 }
 
 fn setup_game(
     mut commands: Commands,
+    mut served: EventWriter<BallServedEvent>,
     assets: Res<Assets>,
+    modes: Res<GameModes>,
+    mut config: ResMut<MatchConfig>,
+    playback: Option<Res<ReplayPlayback>>,
 ) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.spawn_bundle(arena_camera());
 
-    let initial_serve = PlayerSide::random();
+    if !config.arena.fits(config.arena_sides()) {
+        warn!("{} arena doesn't have the right goals for this match, playing in the default one", config.arena.name);
+        config.arena = if config.four_player { Arena::four_player() } else { Arena::classic() };
+    }
+    let mode = match modes.picked(&config) {
+        Some(mode) => mode,
+        None => {
+            let mode = modes.fallback(&config).expect("Some game mode should be playable");
+            warn!("Can't play {} rules in this match, playing {} instead", config.game_mode, mode.id());
+            config.game_mode = mode.id().into();
+            mode
+        }
+    };
+    let arena = &config.arena;
+    commands.insert_resource(ClearColor(arena.background()));
+
+    let seed = config.seed.unwrap_or_else(|| fastrand::u64(..));
+    let mut rng = GameRng::with_seed(seed);
+    let sides = config.sides();
+    let initial_serve = PlayerSide::random(sides, &mut rng);
 
     // Bouncy ball
-    // let ball_bundle = BallBundle::new(Vec3::ZERO, Vec3::new(-1.0, 2.0, 0.0).normalize() * BALL_SPEED);
-    let ball_bundle = BallBundle::from_side(initial_serve);
-    commands.spawn_bundle(ball_bundle);
-
-    // Top wall
-    let wall_bundle = WallBundle::new(Vec3::new(0.0, (WINDOW_SIZE.1 / 2.0) - (WALL_SIZE.1 / 2.0), 0.0));
-    let _top_wall = commands
-        .spawn_bundle(wall_bundle)
-        .id();
+    // let ball_bundle = BallBundle::new(Vec2::ZERO, Vec2::new(-1.0, 2.0).normalize() * BALL_SPEED);
+    let ball_bundle = BallBundle::from_side(initial_serve, config.ball_speed, &mut rng);
+    let served_velocity = ball_bundle.velocity.linear;
+    let ball = commands.spawn_bundle(ball_bundle).id();
 
-    // Bottom wall
-    let wall_bundle = WallBundle::new(Vec3::new(0.0, -(WINDOW_SIZE.1 / 2.0) + (WALL_SIZE.1 / 2.0), 0.0));
-    let _bottom_wall = commands
-        .spawn_bundle(wall_bundle)
-        .id();
+    for wall in &arena.walls {
+        commands.spawn_bundle(WallBundle::new(wall, Color::WHITE));
+    }
+    for obstacle in &arena.obstacles {
+        commands.spawn_bundle(WallBundle::new(obstacle, OBSTACLE_COLOR));
+    }
 
-    // Left goal zone
-    let left_goal = commands
-        .spawn_bundle((
-            Transform::from_translation(Vec3::new(-(WINDOW_SIZE.0 / 2.0) - (GOAL_WIDTH / 2.0) - GOAL_DEPTH, 0.0, 0.0)),
-            GlobalTransform::default(),
-        ))
-        .insert(Goal)
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3::new(GOAL_WIDTH / 2.0, WINDOW_SIZE.1 / 2.0, 0.0),
-            border_radius: None,
-        })
-        .insert(RigidBody::Sensor)
-        .id();
+    // Goal zones, just behind each side's goal line, and walls across the goals nobody plays in
+    for goal in &arena.goals {
+        if !sides.contains(&goal.side) {
+            commands.spawn_bundle(WallBundle::new(&goal.wall(), Color::WHITE));
+            continue;
+        }
+        let (goal, walls) = goal.narrowed(config.handicap(goal.side).goal_width);
+        for wall in &walls {
+            commands.spawn_bundle(WallBundle::new(wall, Color::WHITE));
+        }
+        let sensor = goal.sensor();
+        commands
+            .spawn_bundle((
+                Transform::from_translation(sensor.position().extend(0.0)),
+                GlobalTransform::default(),
+            ))
+            .insert(Goal { side: goal.side })
+            .insert(Position(sensor.position()))
+            .insert(Collider::cuboid(sensor.size()))
+            .insert(Sensor);
+    }
 
-    // Right goal zone
-    let right_goal = commands
-        .spawn_bundle((
-            Transform::from_translation(Vec3::new((WINDOW_SIZE.0 / 2.0) + (GOAL_WIDTH / 2.0) + GOAL_DEPTH, 0.0, 0.0)),
-            GlobalTransform::default(),
-        ))
-        .insert(Goal)
-        .insert(CollisionShape::Cuboid {
-            half_extends: Vec3::new(GOAL_WIDTH / 2.0, WINDOW_SIZE.1 / 2.0, 0.0),
-            border_radius: None,
-        })
-        .insert(RigidBody::Sensor)
-        .id();
+    // Paddles, with every extra one on a side further out from its goal
+    let mut paddles = Vec::with_capacity(config.player_count());
+    for player in 0..config.player_count() {
+        let side = config.player_side(player);
+        let controller = match (&playback, config.mode) {
+            (Some(_), _) => Controller::Replay,
+            (None, MatchMode::Network) => Controller::Network,
+            (None, _) if config.is_cpu(side) => Controller::Ai(config.ai_settings(side)),
+            (None, _) => {
+                let (up, down) = config.keys(player);
+                Controller::Keyboard { up, down, action: config.action_key(player) }
+            }
+        };
+        let slot = (player / sides.len()) as u8;
+        let lane = arena.lane(side).expect("Arena should have a lane for every side");
+        let start = Vec2::from(lane.position);
+        let handicap = config.handicap(side);
+        let length = (PADDLE_SIZE.1 * handicap.paddle_length).min(lane.length);
+        let paddle = Paddle {
+            side,
+            slot,
+            lane_middle: start.dot(side.lane()),
+            lane_length: lane.length,
+            length,
+            base_length: length,
+            speed: PADDLE_SPEED * handicap.paddle_speed,
+            reversed: false,
+        };
+        let translation = start - side.direction() * (slot as f32 * TEAM_PADDLE_SPACING);
+        let paddle_bundle = PaddleBundle::new(translation, paddle, controller, config.color(player));
+        paddles.push(commands.spawn_bundle(paddle_bundle).id());
+    }
 
-    // Left paddle
-    let paddle_bundle = PaddleBundle::new(Vec3::new(-(WINDOW_SIZE.0 / 2.0) + (PADDLE_SIZE.0 / 2.0) + PADDLE_GOAL_SEPARATION, 0.0, 0.0));
-    let left_paddle = commands.spawn_bundle(paddle_bundle).id();
+    // The first serve, off the paddle across from the receiver if the match is played that way
+    let server = match config.serve {
+        ServeStyle::Center => None,
+        ServeStyle::Paddle => server_side(initial_serve, |side| sides.contains(&side)),
+    };
+    match server {
+        Some(side) => {
+            let lane = arena.lane(side).expect("Arena should have a lane for every side");
+            let translation = Vec2::from(lane.position) - side.direction() * SERVE_DISTANCE;
+            commands
+                .entity(ball)
+                .insert(Serving::new(paddles[player_index(side, 0)]))
+                .insert(Position(translation))
+                .insert(PreviousPosition(translation))
+                .insert(Velocity::from_linear(Vec2::ZERO));
+        }
+        None if config.countdown > 0 => {
+            commands
+                .entity(ball)
+                .insert(ServeCountdown::new(config.countdown, served_velocity))
+                .insert(Velocity::from_linear(Vec2::ZERO));
+        }
+        None => served.send(BallServedEvent { toward: initial_serve, velocity: served_velocity }),
+    }
 
-    // Right paddle
-    let paddle_bundle = PaddleBundle::new(Vec3::new((WINDOW_SIZE.0 / 2.0) - (PADDLE_SIZE.0 / 2.0) - PADDLE_GOAL_SEPARATION, 0.0, 0.0));
-    let right_paddle = commands.spawn_bundle(paddle_bundle).id();
+    let mut game_state = GameState::new(seed, paddles, initial_serve);
+    mode.setup(&config, &mut game_state);
+    for &side in sides {
+        game_state.add_points(side, config.handicap(side).starting_points);
+    }
+    game_state.next_serve = mode.next_serve(&config, &game_state);
 
-    // Score text
+    // Score text, or lives left in modes that count them
     let style = TextStyle {
         font: assets.font.clone(),
         font_size: 60.0,
@@ -283,211 +1259,668 @@ fn setup_game(
         horizontal: HorizontalAlign::Center,
         ..default()
     };
-    let left_score_text = commands
-        .spawn_bundle(Text2dBundle {
-            text: Text::with_section("0", style.clone(), alignment),
-            transform: Transform::from_translation(Vec3::new(-200.0, 200.0, 0.0)),
-            ..default()
+    game_state.score_texts = sides
+        .iter()
+        .map(|&side| {
+            let text = commands
+                .spawn_bundle(Text2dBundle {
+                    text: Text::with_section(game_state.score(side).to_string(), style.clone(), alignment),
+                    transform: Transform::from_translation(score_position(side, &config).extend(0.0)),
+                    ..default()
+                })
+                .id();
+            (side, text)
         })
-        .id();
-    let right_score_text = commands
+        .collect();
+
+    // Player names, under the scores
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+    for player in 0..config.player_count() {
+        let style = TextStyle {
+            font: assets.font.clone(),
+            font_size: 24.0,
+            color: config.color(player),
+        };
+        let slot = (player / sides.len()) as f32;
+        let position = score_position(config.player_side(player), &config) - Vec2::new(0.0, 80.0 + slot * 28.0);
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(config.player_name(player), style, alignment),
+            transform: Transform::from_translation(position.extend(0.0)),
+            ..default()
+        });
+    }
+
+    // Handicaps, under the names of the sides that have them
+    let style = TextStyle {
+        font: assets.font.clone(),
+        font_size: 18.0,
+        color: Color::GRAY,
+    };
+    let slots = (config.player_count() / sides.len()) as f32;
+    for &side in sides {
+        let handicap = config.handicap(side);
+        if handicap.is_even() {
+            continue;
+        }
+        let position = score_position(side, &config) - Vec2::new(0.0, 80.0 + slots * 28.0);
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(handicap.describe(), style.clone(), alignment),
+            transform: Transform::from_translation(position.extend(0.0)),
+            ..default()
+        });
+    }
+
+    // Shown while the match is paused, over everything else
+    let style = TextStyle {
+        font: assets.font.clone(),
+        font_size: 80.0,
+        color: Color::WHITE,
+    };
+    let hint_style = TextStyle {
+        font_size: 24.0,
+        ..style.clone()
+    };
+    let text = Text {
+        sections: vec![
+            TextSection { value: "Paused".into(), style },
+            TextSection { value: "\nP to resume, Q to end the match".into(), style: hint_style },
+        ],
+        alignment,
+    };
+    commands
         .spawn_bundle(Text2dBundle {
-            text: Text::with_section("0", style.clone(), alignment),
-            transform: Transform::from_translation(Vec3::new(200.0, 200.0, 0.0)),
+            text,
+            transform: Transform::from_xyz(0.0, 0.0, 10.0),
+            visibility: Visibility { is_visible: false },
             ..default()
         })
-        .id();
+        .insert(PauseText);
+
+    commands.insert_resource(rng);
+    commands.insert_resource(game_state);
+}
 
-    commands.insert_resource(GameState {
-        left_paddle,
-        right_paddle,
-        left_goal,
-        right_goal,
-        next_serve: initial_serve.next(),
-        left_score: 0,
-        right_score: 0,
-        left_score_text,
-        right_score_text,
-    });
+#[allow(clippy::type_complexity)]
+fn despawn_game(
+    mut commands: Commands,
+    mut clock: ResMut<SimulationClock>,
+    query: Query<Entity, Or<(With<Camera>, With<Position>, With<Text>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    clock.paused = false;
+    commands.insert_resource(ClearColor(Color::BLACK));
+    commands.remove_resource::<GameState>();
 }
 
 fn ball_wall_bounce(
     mut events: EventReader<CollisionEvent>,
-    mut ball_q: Query<&mut Velocity, With<Ball>>,
+    mut hit_wall: EventWriter<BallHitWallEvent>,
+    mut ball_q: Query<(&mut Position, &mut Velocity), With<Ball>>,
     wall_q: Query<(), With<Wall>>,
 ) {
     for event in events.iter() {
-        if let CollisionEvent::Started(data1, data2) = event {
-            let mut try_bounce = |entity1, entity2| {
-                if let (Ok(mut ball_velocity), Ok(_wall)) = (ball_q.get_mut(entity1), wall_q.get(entity2)) {
-                    // The ball hit a wall, so simply reverse the y velocity.
-                    ball_velocity.linear.y *= -1.0;
-                }
-            };
-
-            let entity1 = data1.rigid_body_entity();
-            let entity2 = data2.rigid_body_entity();
-
-            try_bounce(entity1, entity2);
-            try_bounce(entity2, entity1);
+        if let (Ok((mut ball_position, mut ball_velocity)), Ok(_wall)) = (ball_q.get_mut(event.entity), wall_q.get(event.other)) {
+            // The ball hit a wall, so push it back out and bounce it off the wall where they touched.
+            ball_position.0 += event.normal * event.depth;
+            ball_velocity.linear = reflect(ball_velocity.linear, event.normal);
+            hit_wall.send(BallHitWallEvent { speed: ball_velocity.linear.length() });
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn ball_paddle_bounce(
     mut events: EventReader<CollisionEvent>,
-    mut ball_q: Query<(&Transform, &mut Velocity), With<Ball>>,
-    paddle_q: Query<&Transform, With<Paddle>>,
-    game_state: Res<GameState>,
-    assets: Res<Assets>,
-    audio: Res<Audio>,
+    mut bounced: EventWriter<BallBouncedEvent>,
+    mut game_state: ResMut<GameState>,
+    modes: Res<GameModes>,
+    config: Res<MatchConfig>,
+    mut ball_q: Query<(&mut Position, &mut Velocity, &mut LastHit, &mut Spin), (With<Ball>, Without<Held>, Without<Serving>)>,
+    paddle_q: Query<(&Position, &PaddleMotion, &Paddle), Without<Ball>>,
 ) {
     for event in events.iter() {
-        if let CollisionEvent::Started(data1, data2) = event {
-            let mut try_bounce = |entity1, entity2| {
-                if let (Ok((ball_transform, mut ball_velocity)), Ok(paddle_transform)) = (ball_q.get_mut(entity1), paddle_q.get(entity2)) {
-                    let multiplier = if entity2 == game_state.left_paddle { 1.0 } else { -1.0 };
-
-                    // TODO: If the ball hit the top or bottom of a paddle, reflect the Y velocity.
-                    // The ball hit a paddle. Figure out what new angle to come back at based where they collided.
-                    let distance_from_center = ball_transform.translation.y - paddle_transform.translation.y;
-                    let ratio_from_center = (distance_from_center / (PADDLE_SIZE.1 / 2.0)).clamp(-1.0, 1.0);
-                    let bounce_angle = MAX_BOUNCE_ANGLE * ratio_from_center * multiplier;
-                    let new_direction = Vec2::X * multiplier;
-                    let new_direction = Mat2::from_angle(bounce_angle.to_radians()).mul_vec2(new_direction);
-                    let new_speed = (ball_velocity.linear.length() + BALL_SPEED_INCREMENT).min(BALL_MAX_SPEED);
-                    ball_velocity.linear = new_speed * new_direction.extend(0.0);
-
-                    audio.play(assets.bounce.clone());
-                }
-            };
+        if let (Ok((mut ball_position, mut ball_velocity, mut last_hit, mut spin)), Ok((paddle_position, paddle_motion, paddle))) = (ball_q.get_mut(event.entity), paddle_q.get(event.other)) {
+            // Paddles send the ball away from their own goal.
+            let away = -paddle.side.direction();
+            let lane = paddle.side.lane();
+            // Let a ball returned by a teammate further back pass through from behind.
+            let behind = (ball_position.0 - paddle_position.0).dot(away) < 0.0;
+            if paddle.slot > 0 && behind && ball_velocity.linear.dot(away) > 0.0 {
+                continue;
+            }
+
+            // Move the ball out of the paddle so it isn't hit again next tick.
+            ball_position.0 += event.normal * event.depth;
+
+            // TODO: If the ball hit the end of a paddle, reflect the velocity along its lane.
+            // The ball hit a paddle. Figure out what new angle to come back at based where they collided.
+            let distance_from_center = (ball_position.0 - paddle_position.0).dot(lane);
+            let ratio_from_center = (distance_from_center / (paddle.length / 2.0)).clamp(-1.0, 1.0);
+            let angle = MAX_BOUNCE_ANGLE * ratio_from_center;
+            // Turn toward whichever end of the paddle the ball hit.
+            let bounce_angle = angle * away.perp_dot(lane);
+            let new_direction = Mat2::from_angle(bounce_angle.to_radians()).mul_vec2(away);
+            let new_speed = (ball_velocity.linear.length() + config.speed_increment).min(BALL_MAX_SPEED);
+            ball_velocity.linear = new_speed * new_direction;
+            last_hit.0 = Some(paddle.side);
 
-            let entity1 = data1.rigid_body_entity();
-            let entity2 = data2.rigid_body_entity();
+            // Curve the ball toward wherever the paddle was moving, the faster the more, up to the
+            // configured spin at normal paddle speed. Dashing or faster paddles don't add more.
+            let ratio = (paddle_motion.velocity / PADDLE_SPEED).clamp(-1.0, 1.0);
+            spin.0 = ratio * config.spin.to_radians() * away.perp_dot(lane);
 
-            try_bounce(entity1, entity2);
-            try_bounce(entity2, entity1);
+            modes.current(&config).paddle_hit(paddle.side, &mut game_state);
+            bounced.send(BallBouncedEvent { ball: event.entity, paddle: event.other, side: paddle.side, speed: new_speed, angle });
         }
     }
 }
 
+/// Runs every tick, so that bounces heard once aren't heard again when their tick is simulated
+/// again after a rollback.
+fn play_bounce_sound(
+    mut bounced: EventReader<BallBouncedEvent>,
+    assets: Res<Assets>,
+    audio: Res<Audio>,
+    clock: Res<SimulationClock>,
+) {
+    for _ in bounced.iter() {
+        if !clock.resimulating {
+            audio.play(assets.bounce.clone());
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn check_scored(
+    mut commands: Commands,
     mut collisions: EventReader<CollisionEvent>,
     mut player_scored: EventWriter<PlayerScoredEvent>,
+    mut lives_lost: EventWriter<LifeLostEvent>,
+    mut missed: EventWriter<BallMissedEvent>,
     ball_q: Query<(), With<Ball>>,
-    game_state: Res<GameState>,
+    goal_q: Query<&Goal>,
+    mut game_state: ResMut<GameState>,
+    modes: Res<GameModes>,
+    config: Res<MatchConfig>,
 ) {
+    let mut in_play = ball_q.iter().count();
     for event in collisions.iter() {
-        if let CollisionEvent::Started(data1, data2) = event {
-            let mut check_scored_internal = |entity1, entity2| {
-                if let (Ok(()), true) = (ball_q.get(entity1), entity2 == game_state.left_goal) {
-                    player_scored.send(PlayerScoredEvent(PlayerSide::Right));
-                } else if let (Ok(()), true) = (ball_q.get(entity1), entity2 == game_state.right_goal) {
-                    player_scored.send(PlayerScoredEvent(PlayerSide::Left));
-                }
-            };
-
-            let entity1 = data1.rigid_body_entity();
-            let entity2 = data2.rigid_body_entity();
-
-            check_scored_internal(entity1, entity2);
-            check_scored_internal(entity2, entity1);
+        if let (Ok(()), Ok(goal)) = (ball_q.get(event.entity), goal_q.get(event.other)) {
+            match modes.current(&config).goal(goal.side, &config, &mut game_state) {
+                GoalOutcome::Point(scorer) => player_scored.send(PlayerScoredEvent(scorer)),
+                GoalOutcome::LifeLost => lives_lost.send(LifeLostEvent(goal.side)),
+                GoalOutcome::Missed => missed.send(BallMissedEvent(goal.side)),
+            }
+            // Play on with any other balls, and serve again once the last one has gone in.
+            if in_play > 1 {
+                commands.entity(event.entity).despawn_recursive();
+                in_play -= 1;
+            } else {
+                game_state.serve_ball = Some(event.entity);
+            }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn reset_round(
     mut commands: Commands,
     mut player_scored: EventReader<PlayerScoredEvent>,
+    mut lives_lost: EventReader<LifeLostEvent>,
+    mut missed: EventReader<BallMissedEvent>,
+    mut served: EventWriter<BallServedEvent>,
     mut game_state: ResMut<GameState>,
-    ball_q: Query<(Entity, &Velocity), With<Ball>>,
-    mut score_text_q: Query<&mut Text>,
+    mut rng: ResMut<GameRng>,
+    modes: Res<GameModes>,
+    config: Res<MatchConfig>,
+    mut ball_q: Query<(&mut Position, &mut PreviousPosition, &mut Velocity, &mut LastHit, &mut Spin, &mut SpeedScale), With<Ball>>,
+    goal_q: Query<(Entity, &Goal)>,
+    mut paddle_q: Query<(Entity, &Paddle, &mut Position, &mut PreviousPosition, &mut PaddleMotion), Without<Ball>>,
 ) {
-    let should_reset = !player_scored.is_empty();
+    let mut knocked_out = Vec::new();
     for event in player_scored.iter() {
-        match event.0 {
-            PlayerSide::Left => {
-                game_state.left_score += 1;
-                println!("Left Scored! {} - {}", game_state.left_score, game_state.right_score);
-                if let Ok(mut text) = score_text_q.get_mut(game_state.left_score_text) {
-                    text.sections[0].value = game_state.left_score.to_string();
+        game_state.add_points(event.0, 1);
+        info!(
+            "{} Scored! {} - {}",
+            event.0.label(),
+            game_state.score(PlayerSide::Left),
+            game_state.score(PlayerSide::Right),
+        );
+    }
+    for event in lives_lost.iter() {
+        let side = event.0;
+        let lives = &mut game_state.scores[side.index()];
+        *lives = lives.saturating_sub(1);
+        info!("{} lost a life! {} left", side.label(), lives);
+        if *lives > 0 {
+            continue;
+        }
+
+        // The side is out. Wall off its goal and take its paddles away.
+        info!("{} is out!", side.label());
+        knocked_out.push(side);
+        for (entity, goal) in goal_q.iter() {
+            if goal.side == side {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        for (entity, paddle, ..) in paddle_q.iter() {
+            if paddle.side == side {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        if let Some(goal) = config.arena.goal(side) {
+            commands.spawn_bundle(WallBundle::new(&goal.wall(), Color::WHITE));
+        }
+    }
+    for event in missed.iter() {
+        info!("{} missed!", event.0.label());
+    }
+    if let Some(ball) = game_state.serve_ball.take() {
+        // Teleport the ball back to the center and serve it again.
+        if let Ok((mut position, mut previous_position, mut velocity, mut last_hit, mut spin, mut speed_scale)) = ball_q.get_mut(ball) {
+            debug!("Resetting round. Ball speed was: {:0.0}", velocity.linear.length());
+
+            position.0 = Vec2::ZERO;
+            previous_position.0 = Vec2::ZERO;
+            last_hit.0 = None;
+            spin.0 = 0.0;
+            speed_scale.0 = 1.0;
+            if config.reset_paddles {
+                for (_, paddle, mut paddle_position, mut paddle_previous, mut motion) in paddle_q.iter_mut() {
+                    let position = &mut paddle_position.0;
+                    let coordinate = if paddle.side.is_top_or_bottom() { &mut position.x } else { &mut position.y };
+                    *coordinate = paddle.lane_middle;
+                    paddle_previous.0 = *position;
+                    motion.velocity = 0.0;
                 }
             }
-            PlayerSide::Right => {
-                game_state.right_score += 1;
-                println!("Right Scored! {} - {}", game_state.left_score, game_state.right_score);
-                if let Ok(mut text) = score_text_q.get_mut(game_state.right_score_text) {
-                    text.sections[0].value = game_state.right_score.to_string();
+
+            // Or put it in front of the serving paddle, if it's still in the match.
+            let front_paddle = |side| game_state.paddles.get(player_index(side, 0)).and_then(|&entity| paddle_q.get(entity).ok());
+            let server = match config.serve {
+                ServeStyle::Center => None,
+                ServeStyle::Paddle => server_side(game_state.next_serve, |side| {
+                    config.sides().contains(&side) && !knocked_out.contains(&side) && front_paddle(side).is_some()
+                }),
+            };
+            match server.and_then(front_paddle) {
+                Some((entity, paddle, paddle_position, ..)) => {
+                    position.0 = paddle_position.0 - paddle.side.direction() * SERVE_DISTANCE;
+                    previous_position.0 = position.0;
+                    velocity.linear = Vec2::ZERO;
+                    commands.entity(ball).insert(Serving::new(entity));
+                }
+                // Or count down to serving it from the middle.
+                None if config.countdown > 0 => {
+                    let serve = serve_velocity(game_state.next_serve, config.ball_speed, &mut rng);
+                    velocity.linear = Vec2::ZERO;
+                    commands.entity(ball).insert(ServeCountdown::new(config.countdown, serve));
+                }
+                None => {
+                    velocity.linear = serve_velocity(game_state.next_serve, config.ball_speed, &mut rng);
+                    served.send(BallServedEvent { toward: game_state.next_serve, velocity: velocity.linear });
                 }
             }
+            game_state.serve = game_state.next_serve;
+            game_state.next_serve = modes.current(&config).next_serve(&config, &game_state);
         }
     }
-    if should_reset {
-        // TODO: Figure out if we can get teleporting working! Sounds like teleporting won't work for KinematicVelocityBased bodies...
-        // For now, respawn the ball in the center.
-        for (ball_entity, velocity) in ball_q.iter() {
-            eprintln!("Resetting round. Ball speed was: {:0.0}", velocity.linear.length());
+}
+
+/// Pause and unpause with P, and end the match early with Q while paused, for matches that
+/// would otherwise go on forever. Network matches can't be paused, since the other player's
+/// would carry on.
+fn toggle_pause(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    config: Res<MatchConfig>,
+    mut clock: ResMut<SimulationClock>,
+    mut text_q: Query<&mut Visibility, With<PauseText>>,
+) {
+    if config.mode == MatchMode::Network {
+        return;
+    }
+    if clock.paused && keys.just_pressed(KeyCode::Q) {
+        commands.insert_resource(NextState(AppState::MatchOver));
+        return;
+    }
+    if !keys.just_pressed(KeyCode::P) {
+        return;
+    }
+    clock.paused = !clock.paused;
+    for mut visibility in text_q.iter_mut() {
+        visibility.is_visible = clock.paused;
+    }
+}
 
-            commands.entity(ball_entity).despawn();
-            let ball_bundle = BallBundle::from_side(game_state.next_serve);
-            commands.spawn_bundle(ball_bundle);
-            game_state.next_serve = game_state.next_serve.next();
+fn update_score_text(
+    game_state: Res<GameState>,
+    mut score_text_q: Query<&mut Text>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    for &(side, entity) in &game_state.score_texts {
+        if let Ok(mut text) = score_text_q.get_mut(entity) {
+            let score = game_state.score(side).to_string();
+            if text.sections[0].value != score {
+                text.sections[0].value = score;
+            }
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
+fn check_match_over(
+    mut commands: Commands,
+    mut match_over: EventWriter<MatchOverEvent>,
+    mut game_state: ResMut<GameState>,
+    modes: Res<GameModes>,
+    config: Res<MatchConfig>,
+    serving_q: Query<(), Or<(With<Serving>, With<ServeCountdown>)>>,
+) {
+    game_state.tick += 1;
+    if serving_q.is_empty() {
+        game_state.play_ticks += 1;
+    }
+
+    let winner = match modes.current(&config).winner(&config, &game_state) {
+        Some(winner) => winner,
+        None => return,
+    };
+
+    info!("{} wins! {:?}", winner.label(), game_state.scores);
+    match_over.send(MatchOverEvent { winner });
+    commands.insert_resource(NextState(AppState::MatchOver));
+}
+
 fn camera_control(
     keys: Res<Input<KeyCode>>,
     buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
     mut motion_events: EventReader<MouseMotion>,
-    mut camera_q: Query<&mut Transform, With<Camera>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
 ) {
-    let mut camera_transform = camera_q.single_mut();
+    let (mut camera_transform, mut projection) = camera_q.single_mut();
 
     if keys.just_pressed(KeyCode::Key0) {
         camera_transform.translation.x = 0.0;
         camera_transform.translation.y = 0.0;
+        projection.scale = WINDOW_SIZE.1 / 2.0;
     }
 
-    if buttons.pressed(MouseButton::Left) {
-        let mouse_delta = {
-            let mut delta = Vec2::ZERO;
-            for motion in motion_events.iter() {
-                delta.x -= motion.delta.x;
-                delta.y += motion.delta.y;
-            }
-            delta.extend(0.0)
-        };
+    let drag = buttons.pressed(MouseButton::Left);
+    pan_and_zoom(&mut camera_transform, &mut projection, &windows, drag, &mut motion_events, &mut wheel_events);
+}
+
+/// Move an `arena_camera` by how far the mouse is dragged, and zoom it in and out with the
+/// mouse wheel.
+pub fn pan_and_zoom(
+    camera_transform: &mut Transform,
+    projection: &mut OrthographicProjection,
+    windows: &Windows,
+    drag: bool,
+    motion_events: &mut EventReader<MouseMotion>,
+    wheel_events: &mut EventReader<MouseWheel>,
+) {
+    // The projection's scale is half the height of the view, in arena units.
+    let units_per_pixel = windows
+        .get_primary()
+        .map_or(1.0, |window| projection.scale * 2.0 / window.height());
+
+    let mut delta = Vec2::ZERO;
+    for motion in motion_events.iter() {
+        delta.x -= motion.delta.x;
+        delta.y += motion.delta.y;
+    }
+    if drag {
         // Move the camera by how much the mouse moved.
-        camera_transform.translation += mouse_delta;
+        camera_transform.translation += (delta * units_per_pixel).extend(0.0);
+    }
+
+    for wheel in wheel_events.iter() {
+        let zoom = 1.1_f32.powf(-wheel.y.signum());
+        projection.scale = (projection.scale * zoom).clamp(WINDOW_SIZE.1 / 8.0, WINDOW_SIZE.1 * 4.0);
     }
 }
 
-fn paddle_control(
+fn keyboard_input(
     keys: Res<Input<KeyCode>>,
-    mut paddle_q: Query<&mut Transform, With<Paddle>>,
+    mut paddle_q: Query<(&Controller, &mut PaddleInput)>,
+) {
+    for (controller, mut input) in paddle_q.iter_mut() {
+        if let Controller::Keyboard { up, down, action } = *controller {
+            input.movement = keys.pressed(up) as i8 - keys.pressed(down) as i8;
+            input.action = keys.pressed(action);
+        }
+    }
+}
+
+/// Turn spinning balls, and wear their spin down.
+fn curve_balls(
+    config: Res<MatchConfig>,
+    mut ball_q: Query<(&mut Velocity, &mut Spin)>,
+) {
+    let kept = (1.0 - config.spin_decay.clamp(0.0, 1.0)).powf(TICK_DELTA);
+    for (mut velocity, mut spin) in ball_q.iter_mut() {
+        if spin.0 == 0.0 {
+            continue;
+        }
+        velocity.linear = Mat2::from_angle(spin.0 * TICK_DELTA).mul_vec2(velocity.linear);
+        spin.0 *= kept;
+    }
+}
+
+fn carry_held_balls(
+    mut commands: Commands,
+    mut ball_q: Query<(Entity, &mut Position, &mut Velocity, &mut Held)>,
+    paddle_q: Query<&Position, (With<Paddle>, Without<Held>)>,
+) {
+    for (entity, mut position, mut velocity, mut held) in ball_q.iter_mut() {
+        if let Ok(paddle_position) = paddle_q.get(held.paddle) {
+            position.0 = paddle_position.0 + held.offset;
+        }
+        if held.ticks_left > 0 {
+            held.ticks_left -= 1;
+            velocity.linear = Vec2::ZERO;
+        } else {
+            velocity.linear = held.velocity;
+            commands.entity(entity).remove::<Held>();
+        }
+    }
+}
+
+/// Keep balls waiting to be served in front of their paddles, and launch them once their player
+/// presses the action key or runs out of time. They go toward wherever the paddle is moving, the
+/// faster the further, like a return off the end of the paddle.
+fn serve_balls(
+    mut commands: Commands,
+    mut served: EventWriter<BallServedEvent>,
+    mut rng: ResMut<GameRng>,
     game_state: Res<GameState>,
-    time: Res<Time>,
+    config: Res<MatchConfig>,
+    mut ball_q: Query<(Entity, &mut Position, &mut Velocity, &mut Serving)>,
+    paddle_q: Query<(&Position, &Paddle, &PaddleMotion, &PaddleInput, &Controller), Without<Serving>>,
 ) {
-    const PADDLE_LIMIT: f32 = (WINDOW_SIZE.1 / 2.0) - WALL_SIZE.1 - (PADDLE_SIZE.1 / 2.0);
+    for (entity, mut position, mut velocity, mut serving) in ball_q.iter_mut() {
+        let (paddle_position, paddle, motion, input, controller) = match paddle_q.get(serving.paddle) {
+            Ok(paddle) => paddle,
+            // The paddle has gone, so serve from wherever the ball is.
+            Err(_) => {
+                velocity.linear = serve_velocity(game_state.serve, config.ball_speed, &mut rng);
+                served.send(BallServedEvent { toward: game_state.serve, velocity: velocity.linear });
+                commands.entity(entity).remove::<Serving>();
+                continue;
+            }
+        };
+        let away = -paddle.side.direction();
+        position.0 = paddle_position.0 + away * SERVE_DISTANCE;
 
-    if let Ok(mut transform) = paddle_q.get_mut(game_state.left_paddle) {
-        if keys.pressed(KeyCode::W) {
-            transform.translation.y += PADDLE_SPEED * time.delta_seconds();
+        let pressed = input.action && !serving.action_held;
+        serving.action_held = input.action;
+        serving.ticks += 1;
+        let timeout = match controller {
+            Controller::Ai(_) => AI_SERVE_DELAY,
+            _ => config.serve_timeout,
+        };
+        if !pressed && serving.ticks < timeout * TICK_RATE {
+            velocity.linear = Vec2::ZERO;
+            continue;
         }
-        if keys.pressed(KeyCode::S) {
-            transform.translation.y -= PADDLE_SPEED * time.delta_seconds();
+
+        let lane = paddle.side.lane();
+        let ratio = (motion.velocity / PADDLE_SPEED).clamp(-1.0, 1.0);
+        let angle = MAX_BOUNCE_ANGLE * ratio * away.perp_dot(lane);
+        let direction = Mat2::from_angle(angle.to_radians()).mul_vec2(away);
+        velocity.linear = direction * config.ball_speed.min(BALL_MAX_SPEED);
+        served.send(BallServedEvent { toward: game_state.serve, velocity: velocity.linear });
+        commands.entity(entity).remove::<Serving>();
+    }
+}
+
+/// Hold balls counting down to a serve still, and serve them once the count is up.
+fn count_down_serves(
+    mut commands: Commands,
+    mut served: EventWriter<BallServedEvent>,
+    game_state: Res<GameState>,
+    mut ball_q: Query<(Entity, &mut Velocity, &mut ServeCountdown)>,
+) {
+    for (entity, mut velocity, mut countdown) in ball_q.iter_mut() {
+        countdown.ticks_left = countdown.ticks_left.saturating_sub(1);
+        if countdown.ticks_left > 0 {
+            velocity.linear = Vec2::ZERO;
+            continue;
         }
-        transform.translation.y = transform.translation.y.clamp(-PADDLE_LIMIT, PADDLE_LIMIT);
+        velocity.linear = countdown.velocity;
+        served.send(BallServedEvent { toward: game_state.serve, velocity: velocity.linear });
+        commands.entity(entity).remove::<ServeCountdown>();
     }
+}
+
+/// Show the seconds left before a serve in the middle of the arena, with an arrow on the ball
+/// pointing the way it's going to go.
+fn show_countdown(
+    mut commands: Commands,
+    assets: Res<Assets>,
+    ball_q: Query<(Entity, &ServeCountdown)>,
+    mut text_q: Query<(Entity, &mut Text), With<CountdownText>>,
+    arrow_q: Query<Entity, With<ServeArrow>>,
+) {
+    let (ball, countdown) = match ball_q.iter().next() {
+        Some(ball) => ball,
+        None => {
+            for entity in text_q.iter().map(|(entity, _)| entity).chain(arrow_q.iter()) {
+                commands.entity(entity).despawn_recursive();
+            }
+            return;
+        }
+    };
 
-    if let Ok(mut transform) = paddle_q.get_mut(game_state.right_paddle) {
-        if keys.pressed(KeyCode::Up) {
-            transform.translation.y += PADDLE_SPEED * time.delta_seconds();
+    let value = countdown.seconds_left().to_string();
+    match text_q.get_single_mut() {
+        Ok((_, mut text)) => {
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
         }
-        if keys.pressed(KeyCode::Down) {
-            transform.translation.y -= PADDLE_SPEED * time.delta_seconds();
+        Err(_) => {
+            let style = TextStyle {
+                font: assets.font.clone(),
+                font_size: 80.0,
+                color: Color::WHITE,
+            };
+            let alignment = TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            };
+            commands
+                .spawn_bundle(Text2dBundle {
+                    text: Text::with_section(value, style, alignment),
+                    transform: Transform::from_xyz(0.0, COUNTDOWN_HEIGHT, 0.0),
+                    ..default()
+                })
+                .insert(CountdownText);
         }
-        transform.translation.y = transform.translation.y.clamp(-PADDLE_LIMIT, PADDLE_LIMIT);
+    }
+
+    if !arrow_q.is_empty() {
+        return;
+    }
+    // A shaft pointing along x, from the edge of the ball, with two strokes swept back from the tip.
+    let (length, thickness) = SERVE_ARROW_SIZE;
+    let start = BALL_SIZE / 2.0 + thickness;
+    let tip = Vec2::new(start + length, 0.0);
+    let stroke = |offset: Vec2, size: Vec2, angle: f32| SpriteBundle {
+        sprite: Sprite {
+            color: Color::WHITE,
+            custom_size: Some(size),
+            ..default()
+        },
+        transform: Transform {
+            translation: offset.extend(0.0),
+            rotation: Quat::from_rotation_z(angle),
+            ..default()
+        },
+        ..default()
+    };
+    let head = length / 3.0;
+    let angle = Vec2::X.angle_between(countdown.velocity);
+    let arrow = commands
+        .spawn_bundle((Transform::from_rotation(Quat::from_rotation_z(angle)), GlobalTransform::default()))
+        .insert(ServeArrow)
+        .with_children(|parent| {
+            parent.spawn_bundle(stroke(Vec2::new(start + length / 2.0, 0.0), Vec2::new(length, thickness), 0.0));
+            for sweep in [-1.0, 1.0] {
+                let angle = sweep * 150_f32.to_radians();
+                let offset = tip + Mat2::from_angle(angle).mul_vec2(Vec2::X) * (head / 2.0);
+                parent.spawn_bundle(stroke(offset, Vec2::new(head, thickness), angle));
+            }
+        })
+        .id();
+    commands.entity(ball).add_child(arrow);
+}
+
+fn paddle_control(
+    config: Res<MatchConfig>,
+    mut paddle_q: Query<(&mut Position, &mut PaddleMotion, &PaddleInput, &Paddle)>,
+) {
+    for (mut position, mut motion, input, paddle) in paddle_q.iter_mut() {
+        let direction = if paddle.reversed { -input.movement } else { input.movement };
+        let velocity = match config.paddle_movement {
+            PaddleMovement::Instant => paddle.speed * direction as f32,
+            PaddleMovement::Inertia => {
+                motion.accelerate(direction, input.action, &config.inertia, paddle.speed / PADDLE_SPEED)
+            }
+        };
+        let (min, max) = (paddle.lane_middle - paddle.reach(), paddle.lane_middle + paddle.reach());
+        let coordinate = if paddle.side.is_top_or_bottom() { &mut position.0.x } else { &mut position.0.y };
+        let moved = *coordinate + velocity * TICK_DELTA;
+        *coordinate = moved.clamp(min, max);
+        // Running into the end of the lane stops the paddle dead.
+        motion.velocity = if *coordinate == moved { velocity } else { 0.0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_saturates() {
+        // Endless breakout matches can rack up more points than a score holds.
+        let mut game_state = GameState::new(0, Vec::new(), PlayerSide::Left);
+        game_state.add_points(PlayerSide::Left, 250);
+        game_state.add_points(PlayerSide::Left, 10);
+        assert_eq!(game_state.score(PlayerSide::Left), u8::MAX);
+        game_state.add_points(PlayerSide::Left, 1);
+        assert_eq!(game_state.score(PlayerSide::Left), u8::MAX);
+        assert_eq!(game_state.score(PlayerSide::Right), 0);
     }
 }
@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ALLOW_EXPORT, AppState,
+    editor::TestPlay,
+    game::{GameState, Handicap, MatchConfig, MatchMode, PlayerSide, player_index},
+    replay::ReplayPlayback,
+    stats::MatchStats,
+};
+
+/// File completed matches are saved to, in the working directory.
+pub const HISTORY_FILE: &str = "match-history.json";
+
+/// Keeps a record of every completed match, saved between runs.
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        let history = if ALLOW_EXPORT {
+            MatchHistory::load(Path::new(HISTORY_FILE)).unwrap_or_else(|e| {
+                warn!("Failed to load match history from {}: {}", HISTORY_FILE, e);
+                MatchHistory::default()
+            })
+        } else {
+            MatchHistory::default()
+        };
+        app
+            .insert_resource(history)
+            .add_exit_system(AppState::InGame, record_match);
+    }
+}
+
+/// A completed match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchRecord {
+    /// When the match ended, in seconds since the Unix epoch.
+    pub played_at: u64,
+    pub mode: MatchMode,
+    /// Player names for each side, in `PlayerSide::index` order. Teams are listed like `Ann & Bob`.
+    pub players: Vec<String>,
+    /// Scores for each side, or lives left in a four-player match.
+    pub scores: Vec<u8>,
+    pub winner: PlayerSide,
+    pub duration_seconds: f32,
+    /// Paddle hits for each side.
+    pub hits: Vec<u32>,
+    pub longest_rally: u32,
+    pub top_ball_speed: f32,
+    /// Handicaps for each side, in the same order as `players`.
+    #[serde(default)]
+    pub handicaps: Vec<Handicap>,
+    /// Profiles playing on each side, in the same order as `players`. Guests and computer
+    /// players have none, so aren't on the leaderboard.
+    #[serde(default)]
+    pub profiles: Vec<Vec<RecordedProfile>>,
+}
+
+/// A profile as it was when a match was played.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedProfile {
+    pub id: u64,
+    pub name: String,
+}
+
+impl MatchRecord {
+    pub fn winner_name(&self) -> &str {
+        &self.players[self.winner.index()]
+    }
+}
+
+/// Every completed match, oldest first.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MatchHistory {
+    pub matches: Vec<MatchRecord>,
+}
+
+/// How a player profile has done across every match in the history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    pub profile: u64,
+    /// The profile's name as of the latest match it played.
+    pub name: String,
+    pub played: u32,
+    pub wins: u32,
+    /// Most matches won in a row.
+    pub best_streak: u32,
+    /// Longest rally in any match the profile played, counting both sides' hits.
+    pub longest_match_rally: u32,
+}
+
+impl MatchHistory {
+    /// Load the history, or start a new one if there's no file yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(io::BufReader::new(file))?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = fs::File::create(path)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Every player profile in the history, best win streak first, then longest rally. In
+    /// doubles, each profile on a side is credited with the side's result.
+    pub fn leaderboard(&self) -> Vec<LeaderboardEntry> {
+        let mut entries: HashMap<u64, (LeaderboardEntry, u32)> = HashMap::new();
+        for record in &self.matches {
+            for (side, profiles) in record.profiles.iter().enumerate() {
+                for profile in profiles {
+                    let (entry, streak) = entries.entry(profile.id).or_insert_with(|| {
+                        let entry = LeaderboardEntry {
+                            profile: profile.id,
+                            name: String::new(),
+                            played: 0,
+                            wins: 0,
+                            best_streak: 0,
+                            longest_match_rally: 0,
+                        };
+                        (entry, 0)
+                    });
+                    entry.name.clone_from(&profile.name);
+                    entry.played += 1;
+                    entry.longest_match_rally = entry.longest_match_rally.max(record.longest_rally);
+                    if record.winner.index() == side {
+                        entry.wins += 1;
+                        *streak += 1;
+                        entry.best_streak = entry.best_streak.max(*streak);
+                    } else {
+                        *streak = 0;
+                    }
+                }
+            }
+        }
+
+        let mut leaderboard: Vec<_> = entries.into_values().map(|(entry, _)| entry).collect();
+        leaderboard.sort_by(|a, b| {
+            b.best_streak
+                .cmp(&a.best_streak)
+                .then(b.longest_match_rally.cmp(&a.longest_match_rally))
+                .then_with(|| a.name.cmp(&b.name))
+                .then(a.profile.cmp(&b.profile))
+        });
+        leaderboard
+    }
+}
+
+/// Format seconds since the Unix epoch as a UTC date and time, like `2022-06-01 18:30`.
+pub fn format_date(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let minutes = seconds % 86400 / 60;
+
+    // Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60)
+}
+
+fn record_match(
+    mut history: ResMut<MatchHistory>,
+    game_state: Res<GameState>,
+    config: Res<MatchConfig>,
+    stats: Res<MatchStats>,
+    playback: Option<Res<ReplayPlayback>>,
+    test_play: Option<Res<TestPlay>>,
+) {
+    // Only keep matches that were played to the end, not watched or played to try out an arena.
+    let winner = match stats.winner {
+        Some(winner) if playback.is_none() && test_play.is_none() => winner,
+        _ => return,
+    };
+    let sides = config.sides();
+
+    let played_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    history.matches.push(MatchRecord {
+        played_at,
+        mode: config.mode,
+        players: sides.iter().map(|&side| config.team_name(side)).collect(),
+        scores: sides.iter().map(|&side| game_state.score(side)).collect(),
+        winner,
+        duration_seconds: stats.duration_seconds(),
+        hits: sides.iter().map(|&side| stats.player(side).hits).collect(),
+        longest_rally: stats.longest_rally(),
+        top_ball_speed: stats.top_ball_speed,
+        handicaps: sides.iter().map(|&side| config.handicap(side)).collect(),
+        profiles: sides
+            .iter()
+            .map(|&side| {
+                (0..config.team_size())
+                    .filter_map(|slot| config.profile(player_index(side, slot)))
+                    .filter(|profile| profile.id != 0)
+                    .map(|profile| RecordedProfile { id: profile.id, name: profile.name.clone() })
+                    .collect()
+            })
+            .collect(),
+    });
+
+    if ALLOW_EXPORT {
+        if let Err(e) = history.save(Path::new(HISTORY_FILE)) {
+            warn!("Failed to save match history to {}: {}", HISTORY_FILE, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(id: u64, name: &str) -> RecordedProfile {
+        RecordedProfile { id, name: name.into() }
+    }
+
+    fn record(winner: PlayerSide, profiles: [Vec<RecordedProfile>; 2], longest_rally: u32) -> MatchRecord {
+        MatchRecord {
+            played_at: 0,
+            mode: MatchMode::Local,
+            players: vec!["Left".into(), "Right".into()],
+            scores: vec![0, 0],
+            winner,
+            duration_seconds: 0.0,
+            hits: vec![0, 0],
+            longest_rally,
+            top_ball_speed: 0.0,
+            handicaps: Vec::new(),
+            profiles: profiles.into(),
+        }
+    }
+
+    #[test]
+    fn leaderboard_streaks() {
+        let (ann, bob) = (profile(1, "Ann"), profile(2, "Bob"));
+        let results = [
+            PlayerSide::Left,
+            PlayerSide::Left,
+            PlayerSide::Right,
+            PlayerSide::Left,
+            PlayerSide::Left,
+            PlayerSide::Left,
+            PlayerSide::Right,
+        ];
+        let history = MatchHistory {
+            matches: results
+                .iter()
+                .map(|&winner| record(winner, [vec![ann.clone()], vec![bob.clone()]], 5))
+                .collect(),
+        };
+
+        let leaderboard = history.leaderboard();
+        assert_eq!(leaderboard.len(), 2);
+        assert_eq!((leaderboard[0].name.as_str(), leaderboard[0].played, leaderboard[0].wins), ("Ann", 7, 5));
+        assert_eq!(leaderboard[0].best_streak, 3);
+        assert_eq!((leaderboard[1].name.as_str(), leaderboard[1].wins), ("Bob", 2));
+        assert_eq!(leaderboard[1].best_streak, 1);
+    }
+
+    #[test]
+    fn leaderboard_doubles() {
+        let (ann, bob, cat) = (profile(1, "Ann"), profile(2, "Bob"), profile(3, "Cat"));
+        let history = MatchHistory {
+            matches: vec![
+                // Bob's partner is a guest, so only Bob is on the right.
+                record(PlayerSide::Left, [vec![ann.clone(), cat.clone()], vec![bob.clone()]], 4),
+                record(PlayerSide::Left, [vec![ann.clone(), cat], vec![bob.clone()]], 9),
+                // Renamed since.
+                record(PlayerSide::Right, [vec![], vec![profile(2, "Rob"), ann]], 2),
+            ],
+        };
+
+        let leaderboard = history.leaderboard();
+        let entry = |id| leaderboard.iter().find(|entry| entry.profile == id).unwrap();
+        assert_eq!(leaderboard.len(), 3);
+        assert_eq!((entry(1).played, entry(1).wins, entry(1).best_streak), (3, 3, 3));
+        assert_eq!((entry(3).played, entry(3).wins, entry(3).best_streak), (2, 2, 2));
+        assert_eq!((entry(2).played, entry(2).wins, entry(2).best_streak), (3, 1, 1));
+        assert_eq!(entry(2).name, "Rob");
+        assert_eq!(entry(1).longest_match_rally, 9);
+        assert_eq!(leaderboard[0].name, "Ann");
+    }
+
+    #[test]
+    fn leaderboard_skips_players_without_profiles() {
+        let history = MatchHistory { matches: vec![record(PlayerSide::Left, [vec![], vec![]], 3)] };
+        assert!(history.leaderboard().is_empty());
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(format_date(0), "1970-01-01 00:00");
+        assert_eq!(format_date(1_654_108_200), "2022-06-01 18:30");
+        assert_eq!(format_date(951_827_400), "2000-02-29 12:30");
+    }
+}
@@ -0,0 +1,60 @@
+
+use bevy::prelude::*;
+use bevy::app::PluginGroupBuilder;
+
+pub mod ai;
+pub mod arena;
+pub mod assets;
+pub mod cli;
+pub mod editor;
+pub mod event_log;
+pub mod game;
+pub mod history;
+pub mod menu;
+pub mod mode;
+pub mod net;
+pub mod physics;
+pub mod power_up;
+pub mod profile;
+pub mod replay;
+pub mod sim;
+pub mod stats;
+pub mod summary;
+
+pub const WINDOW_SIZE: (f32, f32) = (800.0, 600.0);
+pub const ALLOW_EXIT: bool = cfg!(not(target_arch = "wasm32"));
+/// Browsers can't open UDP sockets.
+pub const ALLOW_NETPLAY: bool = cfg!(not(target_arch = "wasm32"));
+/// Browsers can't write files.
+pub const ALLOW_EXPORT: bool = cfg!(not(target_arch = "wasm32"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppState {
+    MainMenu,
+    InGame,
+    MatchOver,
+    Editor,
+}
+
+/// Everything needed to simulate matches, without any menus, rendering or audio setup. Add it
+/// after the `AppState` loopless state.
+pub struct GamePlugins;
+
+impl PluginGroup for GamePlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(sim::SimulationPlugin)
+            .add(game::GamePlugin)
+            .add(mode::classic::ClassicModePlugin)
+            .add(mode::practice::PracticeModePlugin)
+            .add(mode::survival::SurvivalModePlugin)
+            .add(mode::timed::TimedModePlugin)
+            .add(mode::breakout::BreakoutModePlugin)
+            .add(mode::multi_ball::MultiBallModePlugin)
+            .add(power_up::PowerUpPlugin)
+            .add(stats::StatsPlugin)
+            .add(event_log::EventLogPlugin)
+            .add(net::NetPlugin)
+            .add(replay::ReplayPlugin);
+    }
+}
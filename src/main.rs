@@ -3,38 +3,51 @@
 use bevy::prelude::*;
 use iyes_loopless::prelude::*;
 
-mod assets;
-mod game;
-mod menu;
-
-const WINDOW_SIZE: (f32, f32) = (800.0, 600.0);
-const ALLOW_EXIT: bool = cfg!(not(target_arch = "wasm32"));
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum AppState {
-    MainMenu,
-    InGame,
-}
+use bevy_pong::{ALLOW_EXIT, GamePlugins, assets, cli, editor, history, menu, net, profile, replay, summary};
 
 fn main() {
     // When building for WASM, print panics to the browser console.
     #[cfg(target_arch = "wasm32")]
     console_error_panic_hook::set_once();
 
-    let mut app = App::new();
-    app.insert_resource(WindowDescriptor {
-            title: "Pong!".into(),
-            width: WINDOW_SIZE.0,
-            height: WINDOW_SIZE.1,
-            resizable: false,
-            ..default()
+    let args = cli::Args::parse_validated();
+    let replay = args.replay.as_ref().map(|path| {
+        replay::Replay::load(path).unwrap_or_else(|e| {
+            cli::Args::exit_with_error(format!("Failed to load replay {}: {}", path.display(), e))
         })
+    });
+    let lobby = args.net_lobby().map(|lobby| {
+        lobby.unwrap_or_else(|e| cli::Args::exit_with_error(format!("Failed to start network match: {}", e)))
+    });
+
+    let mut app = App::new();
+    app.insert_resource(args.window_descriptor())
         .insert_resource(ClearColor(Color::BLACK))
+        .insert_resource(args.match_config())
+        .insert_resource(args.net_conditions())
+        .insert_resource(args.event_log_settings())
         .add_plugins(DefaultPlugins)
-        .add_loopless_state(AppState::MainMenu)
+        .add_loopless_state(args.initial_state())
         .add_plugin(assets::AssetsPlugin)
         .add_plugin(menu::MenuPlugin)
-        .add_plugin(game::GamePlugin);
+        .add_plugin(summary::SummaryPlugin)
+        .add_plugin(editor::EditorPlugin)
+        .add_plugin(history::HistoryPlugin)
+        .add_plugin(profile::ProfilePlugin)
+        .add_plugin(net::NetDebugPlugin)
+        .add_plugins(GamePlugins);
+    args.check_rules(app.world.resource(), app.world.resource());
+
+    if let Some(replay) = replay {
+        app.insert_resource(replay.config.clone())
+            .insert_resource(replay::ReplayPlayback(replay));
+    }
+    if let Some(lobby) = lobby {
+        app.insert_resource::<net::NetLobby>(lobby);
+    }
+    if let Some(path) = &args.record {
+        app.insert_resource(replay::RecordReplay(path.clone()));
+    }
 
     if ALLOW_EXIT {
         app.add_system(bevy::input::system::exit_on_esc_system);
@@ -1,11 +1,20 @@
+use std::net::IpAddr;
+
 use bevy::prelude::*;
 use bevy::app::AppExit;
 use bevy_egui::{egui, EguiContext, EguiPlugin};
 use iyes_loopless::prelude::*;
 
 use crate::{
-    ALLOW_EXIT, AppState,
+    ALLOW_EXIT, ALLOW_EXPORT, ALLOW_NETPLAY, AppState,
+    arena::Arena,
     assets::Assets,
+    event_log::EventLogSettings,
+    game::{Handicap, MAX_TEAM_SIZE, MatchConfig, MatchMode, PaddleMovement, PlayerSide, ServeStyle},
+    history::{self, MatchHistory},
+    mode::GameModes,
+    net::{self, LobbyRole, NetError, NetLobby},
+    profile::{ControlScheme, Profiles},
 };
 
 pub struct MenuPlugin;
@@ -14,12 +23,45 @@ impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_plugin(EguiPlugin)
+            .init_resource::<MenuState>()
             .add_enter_system(AppState::MainMenu, setup_main_menu)
             .add_exit_system(AppState::MainMenu, despawn_main_menu)
             .add_system(main_menu_ui.run_in_state(AppState::MainMenu));
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuScreen {
+    Main,
+    Host,
+    Join,
+    History,
+    Profiles,
+}
+
+/// What the main menu is showing, and what has been typed into it.
+pub struct MenuState {
+    screen: MenuScreen,
+    port: String,
+    address: String,
+    /// Why the last network match failed to start or ended early.
+    error: Option<String>,
+    /// Address to tell the other player to join, when hosting.
+    lan_ip: Option<IpAddr>,
+}
+
+impl Default for MenuState {
+    fn default() -> Self {
+        Self {
+            screen: MenuScreen::Main,
+            port: net::DEFAULT_PORT.to_string(),
+            address: format!("127.0.0.1:{}", net::DEFAULT_PORT),
+            error: None,
+            lan_ip: if ALLOW_NETPLAY { net::local_ip() } else { None },
+        }
+    }
+}
+
 pub fn setup_main_menu(
     mut commands: Commands,
     assets: Res<Assets>,
@@ -44,6 +86,7 @@ pub fn setup_main_menu(
         });
 }
 
+#[allow(clippy::type_complexity)]
 pub fn despawn_main_menu(
     mut commands: Commands,
     query: Query<Entity, Or<(With<Camera>, With<Text>)>>,
@@ -53,11 +96,41 @@ pub fn despawn_main_menu(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn main_menu_ui(
     mut commands: Commands,
     mut ctx: ResMut<EguiContext>,
     mut exit: EventWriter<AppExit>,
+    mut menu: ResMut<MenuState>,
+    lobby: Option<Res<NetLobby>>,
+    net_error: Option<Res<NetError>>,
+    mut event_log: ResMut<EventLogSettings>,
+    history: Res<MatchHistory>,
+    mut profiles: ResMut<Profiles>,
+    mut config: ResMut<MatchConfig>,
+    arenas: Res<bevy::asset::Assets<Arena>>,
+    modes: Res<GameModes>,
 ) {
+    if let Some(net_error) = net_error {
+        menu.error = Some(net_error.0.clone());
+        commands.remove_resource::<NetError>();
+    }
+
+    if menu.screen == MenuScreen::History {
+        let window = egui::Window::new("History")
+            .title_bar(false)
+            .fixed_size([700.0, 300.0])
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 170.0]);
+        window.show(ctx.ctx_mut(), |ui| history_ui(ui, &history));
+    }
+    if menu.screen == MenuScreen::Profiles {
+        let window = egui::Window::new("Profiles")
+            .title_bar(false)
+            .fixed_size([500.0, 300.0])
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 170.0]);
+        window.show(ctx.ctx_mut(), |ui| profiles_ui(ui, &mut profiles));
+    }
+
     let window = egui::Window::new("Main Menu")
         .title_bar(false)
         .auto_sized()
@@ -66,16 +139,449 @@ pub fn main_menu_ui(
     window.show(ctx.ctx_mut(), |ui| {
         ui.set_width(250.0);
         ui.vertical_centered_justified(|ui| {
-            let play = egui::RichText::new("Play").size(60.0);
-            if ui.button(play).clicked() {
-                commands.insert_resource(NextState(AppState::InGame));
+            if let Some(lobby) = &lobby {
+                let status = match lobby.role {
+                    LobbyRole::Hosting { port } => match menu.lan_ip {
+                        Some(ip) => format!("Waiting for a player to join {}:{}", ip, port),
+                        None => format!("Waiting for a player to join on port {}", port),
+                    },
+                    LobbyRole::Joining { host } => format!("Connecting to {}", host),
+                };
+                ui.label(egui::RichText::new(status).size(20.0));
+                let cancel = egui::RichText::new("Cancel").size(40.0);
+                if ui.button(cancel).clicked() {
+                    commands.remove_resource::<NetLobby>();
+                }
+                return;
+            }
+
+            match menu.screen {
+                MenuScreen::Main => {
+                    layout_picker(ui, &mut config);
+                    if !config.four_player && !config.solo {
+                        ui.add(egui::Slider::new(&mut config.team_size, 1..=MAX_TEAM_SIZE).text("Paddles per side"));
+                    }
+                    rules_picker(ui, &mut config, &modes);
+                    movement_picker(ui, &mut config);
+                    serve_picker(ui, &mut config);
+                    ui.add(egui::Slider::new(&mut config.power_ups.interval, 0..=60).text("Seconds between power-ups (0 for none)"));
+                    ui.add(egui::Slider::new(&mut config.spin, 0.0..=180.0).step_by(15.0).text("Spin from moving paddles (0 for none)"));
+                    arena_picker(ui, &mut config, &arenas);
+                    handicap_picker(ui, &mut config);
+
+                    let selected = profiles.selected.clone();
+                    profiles.selected.resize(config.player_count().max(selected.len()), None);
+                    for player in 0..config.player_count() {
+                        if !config.is_cpu(config.player_side(player)) {
+                            profile_picker(ui, &mut profiles, player);
+                        }
+                    }
+                    if profiles.selected != selected {
+                        profiles.save_and_report();
+                    }
+
+                    let play = egui::RichText::new("Play").size(60.0);
+                    if ui.button(play).clicked() {
+                        config.players = profiles.selection();
+                        match check_players(&config) {
+                            Ok(()) => {
+                                menu.error = None;
+                                commands.insert_resource(NextState(AppState::InGame));
+                            }
+                            Err(e) => menu.error = Some(e),
+                        }
+                    }
+
+                    if ALLOW_NETPLAY {
+                        let host = egui::RichText::new("Host").size(40.0);
+                        if ui.button(host).clicked() {
+                            menu.screen = MenuScreen::Host;
+                            menu.error = None;
+                        }
+                        let join = egui::RichText::new("Join").size(40.0);
+                        if ui.button(join).clicked() {
+                            menu.screen = MenuScreen::Join;
+                            menu.error = None;
+                        }
+                    }
+
+                    let profiles = egui::RichText::new("Profiles").size(40.0);
+                    if ui.button(profiles).clicked() {
+                        menu.screen = MenuScreen::Profiles;
+                        menu.error = None;
+                    }
+
+                    let history = egui::RichText::new("History").size(40.0);
+                    if ui.button(history).clicked() {
+                        menu.screen = MenuScreen::History;
+                        menu.error = None;
+                    }
+
+                    let editor = egui::RichText::new("Arena Editor").size(40.0);
+                    if ui.button(editor).clicked() {
+                        menu.error = None;
+                        commands.insert_resource(NextState(AppState::Editor));
+                    }
+
+                    if ALLOW_EXPORT {
+                        ui.checkbox(&mut event_log.enabled, "Log match events (F2)");
+                    }
+
+                    if ALLOW_EXIT {
+                        let quit = egui::RichText::new("Quit").size(60.0);
+                        if ui.button(quit).clicked() {
+                            exit.send(AppExit);
+                        }
+                    }
+                }
+                MenuScreen::Host => {
+                    ui.label(egui::RichText::new("Port").size(20.0));
+                    ui.text_edit_singleline(&mut menu.port);
+                    let host = egui::RichText::new("Host").size(40.0);
+                    if ui.button(host).clicked() {
+                        let lobby = menu.port
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("{} is not a valid port", menu.port.trim()))
+                            .and_then(|port| NetLobby::host(port).map_err(|e| format!("Failed to host: {}", e)));
+                        match lobby {
+                            Ok(lobby) => {
+                                config.players = profiles.selection();
+                                commands.insert_resource(lobby);
+                                menu.error = None;
+                            }
+                            Err(e) => menu.error = Some(e),
+                        }
+                    }
+                }
+                MenuScreen::History | MenuScreen::Profiles => {}
+                MenuScreen::Join => {
+                    ui.label(egui::RichText::new("Host address").size(20.0));
+                    ui.text_edit_singleline(&mut menu.address);
+                    let join = egui::RichText::new("Join").size(40.0);
+                    if ui.button(join).clicked() {
+                        match NetLobby::join(menu.address.trim()) {
+                            Ok(lobby) => {
+                                config.players = profiles.selection();
+                                commands.insert_resource(lobby);
+                                menu.error = None;
+                            }
+                            Err(e) => menu.error = Some(format!("Failed to join: {}", e)),
+                        }
+                    }
+                }
+            }
+
+            if menu.screen != MenuScreen::Main {
+                let back = egui::RichText::new("Back").size(40.0);
+                if ui.button(back).clicked() {
+                    // Don't leave profiles that can't be told apart.
+                    let valid = match menu.screen {
+                        MenuScreen::Profiles => profiles.validate(),
+                        _ => Ok(()),
+                    };
+                    match valid {
+                        Ok(()) => {
+                            if menu.screen == MenuScreen::Profiles {
+                                profiles.save_and_report();
+                            }
+                            menu.screen = MenuScreen::Main;
+                            menu.error = None;
+                        }
+                        Err(e) => menu.error = Some(e),
+                    }
+                }
+            }
+            if let Some(error) = &menu.error {
+                ui.colored_label(egui::Color32::RED, error);
             }
+        });
+    });
+}
+
+/// Pick from the loaded arenas with goals for the sides being played, switching to one of them if
+/// the current arena doesn't have them.
+fn arena_picker(ui: &mut egui::Ui, config: &mut MatchConfig, arenas: &bevy::asset::Assets<Arena>) {
+    let mut fitting: Vec<_> = arenas
+        .iter()
+        .map(|(_, arena)| arena)
+        .filter(|arena| arena.fits(config.arena_sides()))
+        .collect();
+    fitting.sort_by(|a, b| a.name.cmp(&b.name));
+    if !config.arena.fits(config.arena_sides()) {
+        config.arena = match fitting.first() {
+            Some(&arena) => arena.clone(),
+            None if config.four_player => Arena::four_player(),
+            None => Arena::classic(),
+        };
+    }
+
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new("Arena").size(20.0));
+        egui::ComboBox::from_id_source("arena")
+            .selected_text(config.arena.name.clone())
+            .width(150.0)
+            .show_ui(ui, |ui| {
+                for arena in fitting {
+                    if ui.selectable_label(config.arena == *arena, &arena.name).clicked() {
+                        config.arena = arena.clone();
+                    }
+                }
+            });
+    });
+}
+
+/// Whether to play alone, one side against another, or with four players.
+fn layout_picker(ui: &mut egui::Ui, config: &mut MatchConfig) {
+    ui.horizontal(|ui| {
+        if ui.radio(config.solo, "Solo").clicked() {
+            config.solo = true;
+            config.four_player = false;
+        }
+        if ui.radio(!config.solo && !config.four_player, "Versus").clicked() {
+            config.solo = false;
+            config.four_player = false;
+        }
+        if ui.radio(config.four_player, "Four players").clicked() {
+            config.solo = false;
+            config.four_player = true;
+        }
+    });
+}
+
+fn movement_picker(ui: &mut egui::Ui, config: &mut MatchConfig) {
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new("Paddles").size(20.0));
+        for movement in [PaddleMovement::Instant, PaddleMovement::Inertia] {
+            ui.radio_value(&mut config.paddle_movement, movement, movement.label());
+        }
+    });
+}
 
-            if ALLOW_EXIT {
-                let quit = egui::RichText::new("Quit").size(60.0);
-                if ui.button(quit).clicked() {
-                    exit.send(AppExit);
+fn serve_picker(ui: &mut egui::Ui, config: &mut MatchConfig) {
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new("Serve").size(20.0));
+        for serve in [ServeStyle::Center, ServeStyle::Paddle] {
+            ui.radio_value(&mut config.serve, serve, serve.label());
+        }
+    });
+    let slider = match config.serve {
+        ServeStyle::Center => egui::Slider::new(&mut config.countdown, 0..=5).text("Countdown before serves (0 for none)"),
+        ServeStyle::Paddle => egui::Slider::new(&mut config.serve_timeout, 1..=10).text("Seconds to serve"),
+    };
+    ui.add(slider);
+    ui.checkbox(&mut config.reset_paddles, "Paddles back to the middle after points");
+}
+
+/// Handicaps for the left and the right, folded away until they're wanted.
+#[allow(clippy::type_complexity)]
+fn handicap_picker(ui: &mut egui::Ui, config: &mut MatchConfig) {
+    let sides: Vec<_> = [PlayerSide::Left, PlayerSide::Right]
+        .into_iter()
+        .filter(|side| config.sides().contains(side))
+        .collect();
+    let names: Vec<_> = sides.iter().map(|&side| config.team_name(side)).collect();
+    // Sum them up in the heading, so they're shown before the match even while folded away.
+    let summary: Vec<_> = sides
+        .iter()
+        .zip(&names)
+        .filter(|(&side, _)| !config.handicap(side).is_even())
+        .map(|(&side, name)| format!("{}: {}", name, config.handicap(side).describe()))
+        .collect();
+    let heading = if summary.is_empty() {
+        "Handicaps".to_string()
+    } else {
+        format!("Handicaps ({})", summary.join("; "))
+    };
+
+    egui::CollapsingHeader::new(heading).id_source("handicaps").show(ui, |ui| {
+        egui::Grid::new("handicap_grid").spacing([20.0, 4.0]).show(ui, |ui| {
+            ui.label("");
+            for name in &names {
+                ui.strong(name);
+            }
+            ui.end_row();
+
+            let rows: [(&str, fn(&mut Handicap) -> egui::Slider); 4] = [
+                ("Paddle length", |handicap| egui::Slider::new(&mut handicap.paddle_length, Handicap::PADDLE_RANGE).step_by(0.1)),
+                ("Paddle speed", |handicap| egui::Slider::new(&mut handicap.paddle_speed, Handicap::PADDLE_RANGE).step_by(0.1)),
+                ("Goal width", |handicap| egui::Slider::new(&mut handicap.goal_width, Handicap::GOAL_RANGE).step_by(0.1)),
+                ("Starting points", |handicap| egui::Slider::new(&mut handicap.starting_points, 0..=Handicap::MAX_STARTING_POINTS)),
+            ];
+            for (label, slider) in rows {
+                ui.label(label);
+                for side in &sides {
+                    ui.add(slider(&mut config.handicaps[side.index()]));
+                }
+                ui.end_row();
+            }
+        });
+        if ui.button("Even").clicked() {
+            config.handicaps = Default::default();
+        }
+    });
+}
+
+/// Pick the rules of the match from the game modes that can be played with the other settings,
+/// switching to one of them if the current one can't, then show the picked mode's settings.
+fn rules_picker(ui: &mut egui::Ui, config: &mut MatchConfig, modes: &GameModes) {
+    modes.pick_playable(config);
+    let picked = match modes.picked(config) {
+        Some(mode) => mode,
+        None => return,
+    };
+
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new("Rules").size(20.0));
+        egui::ComboBox::from_id_source("rules")
+            .selected_text(picked.name())
+            .width(150.0)
+            .show_ui(ui, |ui| {
+                let supported: Vec<_> = modes.supporting(config).map(|mode| (mode.id(), mode.name(), mode.description())).collect();
+                for (id, name, description) in supported {
+                    if ui.selectable_label(config.game_mode == id, name).on_hover_text(description).clicked() {
+                        config.game_mode = id.into();
+                    }
+                }
+            });
+    });
+    ui.label(picked.description());
+    picked.settings_ui(ui, config);
+}
+
+/// Combo box picking the profile a player plays as, if any.
+fn profile_picker(ui: &mut egui::Ui, profiles: &mut Profiles, player: usize) {
+    let Profiles { profiles, selected } = profiles;
+    let name = selected[player]
+        .and_then(|index| profiles.get(index))
+        .map_or("Guest", |profile| profile.name.as_str());
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new(format!("Player {}", player + 1)).size(20.0));
+        egui::ComboBox::from_id_source(("player", player))
+            .selected_text(name)
+            .width(150.0)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut selected[player], None, "Guest");
+                for (index, profile) in profiles.iter().enumerate() {
+                    ui.selectable_value(&mut selected[player], Some(index), &profile.name);
+                }
+            });
+    });
+}
+
+/// Make sure everyone playing on this keyboard can be told apart and has their own keys.
+fn check_players(config: &MatchConfig) -> Result<(), String> {
+    let humans: Vec<_> = (0..config.player_count())
+        .filter(|&player| !config.is_cpu(config.player_side(player)))
+        .collect();
+    for (i, &player) in humans.iter().enumerate() {
+        for &other in &humans[..i] {
+            if let (Some(profile), Some(other_profile)) = (config.profile(player), config.profile(other)) {
+                if profile.name == other_profile.name {
+                    return Err(format!("{} can only play as one player.", profile.name));
+                }
+            }
+            if config.controls(player) == config.controls(other) {
+                return Err(format!(
+                    "{} and {} both move with {}.",
+                    config.player_name(other),
+                    config.player_name(player),
+                    config.controls(player).label(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn profiles_ui(ui: &mut egui::Ui, profiles: &mut Profiles) {
+    let mut removed = None;
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::Grid::new("profiles").striped(true).spacing([20.0, 4.0]).show(ui, |ui| {
+            for heading in ["Name", "Colour", "Controls", ""] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for (index, profile) in profiles.profiles.iter_mut().enumerate() {
+                ui.text_edit_singleline(&mut profile.name);
+                ui.color_edit_button_srgb(&mut profile.color);
+                egui::ComboBox::from_id_source(("controls", index))
+                    .selected_text(profile.controls.label())
+                    .show_ui(ui, |ui| {
+                        for controls in ControlScheme::ALL {
+                            ui.selectable_value(&mut profile.controls, controls, controls.label());
+                        }
+                    });
+                if ui.button("Delete").clicked() {
+                    removed = Some(index);
+                }
+                ui.end_row();
+            }
+        });
+        if ui.button("New profile").clicked() {
+            profiles.add();
+        }
+    });
+    if let Some(index) = removed {
+        profiles.remove(index);
+    }
+}
+
+fn history_ui(ui: &mut egui::Ui, history: &MatchHistory) {
+    if history.matches.is_empty() {
+        ui.centered_and_justified(|ui| ui.label("No matches played yet."));
+        return;
+    }
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        ui.heading("Leaderboard");
+        egui::Grid::new("leaderboard").striped(true).spacing([30.0, 4.0]).show(ui, |ui| {
+            for heading in ["Player", "Played", "Wins", "Best streak", "Longest rally in a match"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for entry in history.leaderboard() {
+                ui.label(&entry.name);
+                ui.label(entry.played.to_string());
+                ui.label(entry.wins.to_string());
+                ui.label(entry.best_streak.to_string());
+                ui.label(entry.longest_match_rally.to_string());
+                ui.end_row();
+            }
+        });
+        ui.add_space(12.0);
+
+        ui.heading("Matches");
+        egui::Grid::new("matches").striped(true).spacing([30.0, 4.0]).show(ui, |ui| {
+            for heading in ["Date (UTC)", "Mode", "Players", "Score", "Time", "Longest rally"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for record in history.matches.iter().rev() {
+                let mode = match record.mode {
+                    MatchMode::Local => "Local",
+                    MatchMode::Ai => "vs CPU",
+                    MatchMode::AiVsAi => "CPU vs CPU",
+                    MatchMode::Network => "Network",
+                };
+                let seconds = record.duration_seconds as u32;
+                ui.label(history::format_date(record.played_at));
+                ui.label(mode);
+                let players = ui.label(record.players.join(" vs "));
+                let handicaps: Vec<_> = record.players
+                    .iter()
+                    .zip(&record.handicaps)
+                    .filter(|(_, handicap)| !handicap.is_even())
+                    .map(|(name, handicap)| format!("{}: {}", name, handicap.describe()))
+                    .collect();
+                if !handicaps.is_empty() {
+                    players.on_hover_text(format!("Handicaps\n{}", handicaps.join("\n")));
                 }
+                ui.label(record.scores.iter().map(u8::to_string).collect::<Vec<_>>().join(" - "))
+                    .on_hover_text(format!("{} won", record.winner_name()));
+                ui.label(format!("{}:{:02}", seconds / 60, seconds % 60));
+                ui.label(record.longest_rally.to_string());
+                ui.end_row();
             }
         });
     });
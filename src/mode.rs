@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use crate::game::{GameState, MatchConfig, PlayerSide};
+
+pub mod breakout;
+pub mod classic;
+pub mod multi_ball;
+pub mod practice;
+pub mod survival;
+pub mod timed;
+
+/// Rules of a match: how it starts, who gets served to, what a goal is worth and who wins. Each
+/// mode is added by its own plugin with `GameModeAppExt::add_game_mode`, along with any systems
+/// it needs, and picked with `MatchConfig::game_mode`.
+///
+/// The hooks are called from the simulation, so they must only depend on their arguments for
+/// matches to replay and stay in sync over the network.
+pub trait GameMode: Send + Sync + 'static {
+    /// Stored in `MatchConfig::game_mode`, and given on the command line.
+    fn id(&self) -> &'static str;
+
+    /// Shown in the menu.
+    fn name(&self) -> &'static str;
+
+    /// One line explaining the rules, shown in the menu.
+    fn description(&self) -> &'static str;
+
+    /// Whether the mode can be played with these settings.
+    fn supports(&self, config: &MatchConfig) -> bool;
+
+    /// Whether scores count down the lives each side has left, rather than up the points it has
+    /// won.
+    fn counts_lives(&self) -> bool {
+        false
+    }
+
+    /// Menu controls for the mode's own settings.
+    fn settings_ui(&self, _ui: &mut egui::Ui, _config: &mut MatchConfig) {}
+
+    /// Set up the start of a match, like the scores each side starts on.
+    fn setup(&self, _config: &MatchConfig, _state: &mut GameState) {}
+
+    /// Side to serve toward after the one served toward in `state.serve`.
+    fn next_serve(&self, _config: &MatchConfig, state: &GameState) -> PlayerSide {
+        state.serve.opposite()
+    }
+
+    /// Called when a paddle on a side returns the ball.
+    fn paddle_hit(&self, _side: PlayerSide, _state: &mut GameState) {}
+
+    /// What the ball going into a side's goal does.
+    fn goal(&self, side: PlayerSide, config: &MatchConfig, state: &mut GameState) -> GoalOutcome;
+
+    /// Side that has won the match, if any has yet. The first to `MatchConfig::points_to_win`,
+    /// unless the mode decides otherwise.
+    fn winner(&self, config: &MatchConfig, state: &GameState) -> Option<PlayerSide> {
+        let points_to_win = config.points_to_win?;
+        config.sides().iter().copied().find(|&side| state.score(side) >= points_to_win)
+    }
+}
+
+/// Result of a goal, applied to the scores in `GameState` before the ball is served again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoalOutcome {
+    /// A side wins a point.
+    Point(PlayerSide),
+    /// The side that let the goal in loses a life, and is out once it has none left.
+    LifeLost,
+    /// Nobody scores, and the ball is just served again.
+    Missed,
+}
+
+/// Every game mode that can be played, in the order they were added.
+#[derive(Default)]
+pub struct GameModes {
+    modes: Vec<Box<dyn GameMode>>,
+}
+
+impl GameModes {
+    pub fn get(&self, id: &str) -> Option<&dyn GameMode> {
+        self.modes.iter().find(|mode| mode.id() == id).map(Box::as_ref)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn GameMode> {
+        self.modes.iter().map(Box::as_ref)
+    }
+
+    /// Modes that can be played with these settings.
+    pub fn supporting<'a>(&'a self, config: &'a MatchConfig) -> impl Iterator<Item = &'a dyn GameMode> {
+        self.iter().filter(|mode| mode.supports(config))
+    }
+
+    /// Mode picked for a match, if it exists and can be played with the rest of the settings.
+    pub fn picked(&self, config: &MatchConfig) -> Option<&dyn GameMode> {
+        self.get(&config.game_mode).filter(|mode| mode.supports(config))
+    }
+
+    /// First mode that can be played with these settings, for when the one picked can't.
+    pub fn fallback(&self, config: &MatchConfig) -> Option<&dyn GameMode> {
+        self.iter().find(|mode| mode.supports(config))
+    }
+
+    /// Switch to the fallback mode if the one picked can't be played with the other settings.
+    pub fn pick_playable(&self, config: &mut MatchConfig) {
+        if self.picked(config).is_none() {
+            if let Some(mode) = self.fallback(config) {
+                config.game_mode = mode.id().into();
+            }
+        }
+    }
+
+    /// Mode of the match being played. `setup_game` makes sure there is one.
+    pub fn current(&self, config: &MatchConfig) -> &dyn GameMode {
+        self.get(&config.game_mode).expect("Match should be played with an added game mode")
+    }
+}
+
+pub trait GameModeAppExt {
+    /// Make a game mode available to be picked.
+    fn add_game_mode(&mut self, mode: impl GameMode) -> &mut Self;
+}
+
+impl GameModeAppExt for App {
+    fn add_game_mode(&mut self, mode: impl GameMode) -> &mut Self {
+        let mut modes = self.world.get_resource_or_insert_with(GameModes::default);
+        if modes.get(mode.id()).is_some() {
+            warn!("Game mode {} was already added", mode.id());
+        } else {
+            modes.modes.push(Box::new(mode));
+        }
+        self
+    }
+}
@@ -0,0 +1,232 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use once_cell::sync::Lazy;
+
+use crate::{
+    AppState,
+    arena::{Arena, ArenaBrick},
+    game::{Ball, GameRng, GameState, LastHit, MatchConfig, PlayerSide},
+    mode::{GameMode, GameModeAppExt, GoalOutcome},
+    net::RollbackAppExt,
+    physics::{Collider, CollisionEvent, PhysicsSystem, Velocity, box_overlap, reflect},
+    sim::{Position, SimulationAppExt},
+};
+
+/// Colours of bricks by how many hits they have left, the last for any with more.
+const BRICK_COLORS: &[Color] = &[
+    Color::rgb(0.95, 0.75, 0.3),
+    Color::rgb(0.9, 0.45, 0.25),
+    Color::rgb(0.75, 0.25, 0.45),
+];
+/// Bricks laid in arenas without any of their own, parsed once rather than every field.
+static CLASSIC_BRICKS: Lazy<Vec<ArenaBrick>> = Lazy::new(|| Arena::classic().bricks);
+
+/// Classic rules, with a field of bricks in the middle of the arena. Breaking a brick scores its
+/// points for whoever last hit the ball, and once they're all gone a new field is laid.
+pub struct BreakoutModePlugin;
+
+impl Plugin for BreakoutModePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_game_mode(BreakoutMode)
+            .add_event::<BrickBrokenEvent>()
+            .add_rollback_component::<Brick>()
+            .add_simulation_system(lay_bricks
+                .run_in_state(AppState::InGame)
+                .run_if(is_breakout)
+                .before(PhysicsSystem::DetectCollisions))
+            .add_simulation_system(ball_brick_bounce
+                .run_in_state(AppState::InGame)
+                .run_if(is_breakout)
+                .label("ball_brick_bounce")
+                .after("ball_wall_bounce")
+                .before("ball_paddle_bounce"));
+    }
+}
+
+pub struct BreakoutMode;
+
+impl BreakoutMode {
+    pub const ID: &'static str = "breakout";
+}
+
+impl GameMode for BreakoutMode {
+    fn id(&self) -> &'static str {
+        Self::ID
+    }
+
+    fn name(&self) -> &'static str {
+        "Breakout"
+    }
+
+    fn description(&self) -> &'static str {
+        "Break the bricks in the middle for points, on top of the usual ones for goals."
+    }
+
+    fn supports(&self, config: &MatchConfig) -> bool {
+        !config.four_player && !config.solo
+    }
+
+    fn goal(&self, side: PlayerSide, _config: &MatchConfig, _state: &mut GameState) -> GoalOutcome {
+        GoalOutcome::Point(side.opposite())
+    }
+}
+
+fn is_breakout(config: Res<MatchConfig>) -> bool {
+    config.game_mode == BreakoutMode::ID
+}
+
+/// Sent when the ball breaks a brick.
+pub struct BrickBrokenEvent {
+    /// Where the brick was.
+    pub position: Vec2,
+    /// Side that last hit the ball, and scored the brick's points.
+    pub by: Option<PlayerSide>,
+    /// Whether the brick dropped a power-up.
+    pub power_up: bool,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Brick {
+    pub hit_points: u8,
+    pub points: u8,
+    pub power_up_chance: f32,
+}
+
+impl Brick {
+    fn color(&self) -> Color {
+        let index = (self.hit_points as usize).clamp(1, BRICK_COLORS.len()) - 1;
+        BRICK_COLORS[index]
+    }
+}
+
+#[derive(Bundle)]
+struct BrickBundle {
+    brick: Brick,
+    #[bundle]
+    sprite_bundle: SpriteBundle,
+    position: Position,
+    collider: Collider,
+}
+
+impl BrickBundle {
+    fn new(layout: &ArenaBrick) -> Self {
+        let brick = Brick {
+            hit_points: layout.hit_points,
+            points: layout.points,
+            power_up_chance: layout.power_up_chance,
+        };
+        let rect = &layout.rect;
+        let sprite_bundle = SpriteBundle {
+            sprite: Sprite {
+                color: brick.color(),
+                custom_size: Some(rect.size()),
+                ..default()
+            },
+            transform: Transform {
+                translation: rect.position().extend(0.0),
+                rotation: Quat::from_rotation_z(rect.rotation()),
+                ..default()
+            },
+            ..default()
+        };
+        Self {
+            brick,
+            sprite_bundle,
+            position: Position(rect.position()),
+            collider: Collider::cuboid(rect.size()).with_rotation(rect.rotation()),
+        }
+    }
+}
+
+/// Lay the arena's bricks whenever there are none left, including at the start of the match.
+/// Arenas without any get the classic arena's. Waits for every ball to be clear of where they go,
+/// so none are laid on top of one.
+fn lay_bricks(
+    mut commands: Commands,
+    config: Res<MatchConfig>,
+    brick_q: Query<(), With<Brick>>,
+    ball_q: Query<(&Position, &Collider), With<Ball>>,
+) {
+    if !brick_q.is_empty() {
+        return;
+    }
+    let bricks = if config.arena.bricks.is_empty() {
+        &CLASSIC_BRICKS
+    } else {
+        &config.arena.bricks
+    };
+    let bundles: Vec<_> = bricks.iter().map(BrickBundle::new).collect();
+    let blocked = bundles.iter().any(|bundle| {
+        ball_q.iter().any(|(position, collider)| {
+            box_overlap(position.0, collider, bundle.position.0, &bundle.collider).is_some()
+        })
+    });
+    if blocked {
+        return;
+    }
+    for bundle in bundles {
+        commands.spawn_bundle(bundle);
+    }
+}
+
+fn ball_brick_bounce(
+    mut commands: Commands,
+    mut events: EventReader<CollisionEvent>,
+    mut broken: EventWriter<BrickBrokenEvent>,
+    mut game_state: ResMut<GameState>,
+    mut rng: ResMut<GameRng>,
+    mut ball_q: Query<(&mut Position, &mut Velocity, &LastHit), With<Ball>>,
+    mut brick_q: Query<(&Position, &mut Brick, &mut Sprite), Without<Ball>>,
+) {
+    for event in events.iter() {
+        if let (Ok((mut ball_position, mut ball_velocity, last_hit)), Ok((brick_position, mut brick, mut sprite))) = (ball_q.get_mut(event.entity), brick_q.get_mut(event.other)) {
+            // Bounce off like a wall.
+            ball_position.0 += event.normal * event.depth;
+            ball_velocity.linear = reflect(ball_velocity.linear, event.normal);
+
+            // Already broken by another hit this tick.
+            if brick.hit_points == 0 {
+                continue;
+            }
+            brick.hit_points -= 1;
+            if brick.hit_points > 0 {
+                sprite.color = brick.color();
+                continue;
+            }
+
+            commands.entity(event.other).despawn_recursive();
+            if let Some(side) = last_hit.0 {
+                game_state.add_points(side, brick.points);
+            }
+            let power_up = brick.power_up_chance > 0.0 && rng.f32() < brick.power_up_chance;
+            broken.send(BrickBrokenEvent { position: brick_position.0, by: last_hit.0, power_up });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bricks_wait_for_ball() {
+        let mut world = World::new();
+        world.insert_resource(MatchConfig::default());
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(lay_bricks);
+
+        // Sitting where one of the classic arena's bricks goes.
+        let brick = Arena::classic().bricks[0].rect.position();
+        let ball = world
+            .spawn()
+            .insert_bundle((Ball, Position(brick), Collider::cuboid(Vec2::splat(10.0))))
+            .id();
+        stage.run(&mut world);
+        assert_eq!(world.query::<&Brick>().iter(&world).count(), 0);
+
+        world.get_mut::<Position>(ball).unwrap().0 = Vec2::ZERO;
+        stage.run(&mut world);
+        assert_eq!(world.query::<&Brick>().iter(&world).count(), Arena::classic().bricks.len());
+    }
+}
@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+
+use crate::{
+    game::{GameState, MatchConfig, PlayerSide},
+    mode::{GameMode, GameModeAppExt, GoalOutcome},
+};
+
+/// Two sides, each scoring a point when the ball goes into the other's goal.
+pub struct ClassicModePlugin;
+
+impl Plugin for ClassicModePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_game_mode(ClassicMode);
+    }
+}
+
+pub struct ClassicMode;
+
+impl ClassicMode {
+    pub const ID: &'static str = "classic";
+}
+
+impl GameMode for ClassicMode {
+    fn id(&self) -> &'static str {
+        Self::ID
+    }
+
+    fn name(&self) -> &'static str {
+        "Classic"
+    }
+
+    fn description(&self) -> &'static str {
+        "Score a point every time the ball gets past the other side."
+    }
+
+    fn supports(&self, config: &MatchConfig) -> bool {
+        !config.four_player && !config.solo
+    }
+
+    fn goal(&self, side: PlayerSide, _config: &MatchConfig, _state: &mut GameState) -> GoalOutcome {
+        GoalOutcome::Point(side.opposite())
+    }
+}
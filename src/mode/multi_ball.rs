@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState,
+    game::{BALL_SIZE, Ball, BallBouncedEvent, BallBundle, BallServedEvent, GameRng, GameState, MatchConfig, PlayerSide},
+    mode::{GameMode, GameModeAppExt, GoalOutcome},
+    net::RollbackAppExt,
+    physics::{Collider, CollisionEvent, Sensor, Velocity, box_overlap},
+    sim::{Position, SimulationAppExt, TICK_RATE},
+};
+
+/// Furthest from the middle along the lane an extra ball is put, in spots a ball and a half apart,
+/// when there's something in the way.
+const MAX_SPAWN_OFFSET: i32 = 4;
+
+/// Classic rules, with extra balls coming into play as the rally goes on. Every ball scores on
+/// its own, and the ball is only served again once they've all gone in.
+pub struct MultiBallModePlugin;
+
+impl Plugin for MultiBallModePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_game_mode(MultiBallMode)
+            .add_rollback_resource::<ExtraBalls>()
+            .add_enter_system(AppState::InGame, reset_extra_balls)
+            .add_simulation_system(ball_ball_bounce
+                .run_in_state(AppState::InGame)
+                .run_if(balls_collide)
+                .after("ball_wall_bounce")
+                .before("ball_paddle_bounce"))
+            .add_simulation_system(add_extra_balls
+                .run_in_state(AppState::InGame)
+                .run_if(is_multi_ball)
+                .after("reset_round")
+                .before("check_match_over"));
+    }
+}
+
+/// When extra balls come into play.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MultiBallSettings {
+    /// Most balls in play at once.
+    pub max_balls: u8,
+    /// Seconds between extra balls. Never adds any on a timer when 0.
+    pub interval: u32,
+    /// Paddle hits between extra balls. Never adds any for hits when 0.
+    pub rally_milestone: u32,
+    /// Whether the balls bounce off each other, rather than passing through.
+    pub collisions: bool,
+}
+
+impl Default for MultiBallSettings {
+    fn default() -> Self {
+        Self {
+            max_balls: 3,
+            interval: 10,
+            rally_milestone: 6,
+            collisions: true,
+        }
+    }
+}
+
+pub struct MultiBallMode;
+
+impl MultiBallMode {
+    pub const ID: &'static str = "multiball";
+}
+
+impl GameMode for MultiBallMode {
+    fn id(&self) -> &'static str {
+        Self::ID
+    }
+
+    fn name(&self) -> &'static str {
+        "Multi-ball"
+    }
+
+    fn description(&self) -> &'static str {
+        "More balls join the rally over time, and each one scores on its own."
+    }
+
+    fn supports(&self, config: &MatchConfig) -> bool {
+        !config.four_player && !config.solo
+    }
+
+    fn settings_ui(&self, ui: &mut egui::Ui, config: &mut MatchConfig) {
+        let settings = &mut config.multi_ball;
+        ui.add(egui::Slider::new(&mut settings.max_balls, 2..=8).text("Most balls"));
+        ui.add(egui::Slider::new(&mut settings.interval, 0..=60).text("Seconds between balls (0 for never)"));
+        ui.add(egui::Slider::new(&mut settings.rally_milestone, 0..=20).text("Hits between balls (0 for never)"));
+        ui.checkbox(&mut settings.collisions, "Balls bounce off each other");
+    }
+
+    fn goal(&self, side: PlayerSide, _config: &MatchConfig, _state: &mut GameState) -> GoalOutcome {
+        GoalOutcome::Point(side.opposite())
+    }
+}
+
+fn is_multi_ball(config: Res<MatchConfig>) -> bool {
+    config.game_mode == MultiBallMode::ID
+}
+
+fn balls_collide(config: Res<MatchConfig>) -> bool {
+    config.game_mode == MultiBallMode::ID && config.multi_ball.collisions
+}
+
+/// Progress toward the next extra ball. Starts over whenever the ball is served.
+#[derive(Debug, Default, Clone)]
+struct ExtraBalls {
+    ticks: u32,
+    hits: u32,
+}
+
+fn reset_extra_balls(mut commands: Commands) {
+    commands.insert_resource(ExtraBalls::default());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_extra_balls(
+    mut commands: Commands,
+    mut served: EventReader<BallServedEvent>,
+    mut bounced: EventReader<BallBouncedEvent>,
+    mut extra: ResMut<ExtraBalls>,
+    mut rng: ResMut<GameRng>,
+    config: Res<MatchConfig>,
+    ball_q: Query<(), With<Ball>>,
+    collider_q: Query<(&Position, &Collider), Without<Sensor>>,
+) {
+    if served.iter().count() > 0 {
+        *extra = ExtraBalls::default();
+    }
+    extra.ticks += 1;
+    extra.hits += bounced.iter().count() as u32;
+
+    let settings = &config.multi_ball;
+    let timer_done = settings.interval > 0 && extra.ticks >= settings.interval * TICK_RATE;
+    let milestone_reached = settings.rally_milestone > 0 && extra.hits >= settings.rally_milestone;
+    if !timer_done && !milestone_reached {
+        return;
+    }
+    // Start counting toward the next one, even if there's no room for this one.
+    *extra = ExtraBalls::default();
+    if ball_q.iter().count() >= settings.max_balls as usize {
+        return;
+    }
+    let side = PlayerSide::random(config.sides(), &mut rng);
+
+    // In the middle, or the closest spot to it along the lane that's clear of the other balls
+    // and anything else solid.
+    let collider = Collider::cuboid(Vec2::splat(BALL_SIZE));
+    let spot = (0..=MAX_SPAWN_OFFSET * 2)
+        .map(|i| {
+            let steps = if i % 2 == 0 { -i / 2 } else { (i + 1) / 2 };
+            side.lane() * (steps as f32 * BALL_SIZE * 1.5)
+        })
+        .find(|&spot| {
+            !collider_q.iter().any(|(position, other)| box_overlap(spot, &collider, position.0, other).is_some())
+        });
+    if let Some(spot) = spot {
+        commands.spawn_bundle(BallBundle::from_side(side, config.ball_speed, &mut rng).at(spot));
+    }
+}
+
+/// Bounce balls that run into each other apart, like equally heavy billiard balls.
+fn ball_ball_bounce(
+    mut events: EventReader<CollisionEvent>,
+    mut ball_q: Query<(&mut Position, &mut Velocity), With<Ball>>,
+) {
+    for event in events.iter() {
+        // Every pair of balls is reported both ways round, so only handle it once.
+        if event.entity > event.other {
+            continue;
+        }
+        let (other_velocity, entity_velocity) = match (ball_q.get(event.other), ball_q.get(event.entity)) {
+            (Ok((_, other)), Ok((_, entity))) => (other.linear, entity.linear),
+            _ => continue,
+        };
+
+        // Swap how fast they're going along the normal if they're heading into each other.
+        let closing = (entity_velocity - other_velocity).dot(event.normal);
+        let exchange = if closing < 0.0 { event.normal * closing } else { Vec2::ZERO };
+        let push = event.normal * (event.depth / 2.0);
+        if let Ok((mut position, mut velocity)) = ball_q.get_mut(event.entity) {
+            position.0 += push;
+            velocity.linear -= exchange;
+        }
+        if let Ok((mut position, mut velocity)) = ball_q.get_mut(event.other) {
+            position.0 -= push;
+            velocity.linear += exchange;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+
+    #[test]
+    fn extra_ball_avoids_middle() {
+        let mut world = World::new();
+        world.insert_resource(MatchConfig {
+            multi_ball: MultiBallSettings { interval: 0, rally_milestone: 1, ..default() },
+            ..default()
+        });
+        world.insert_resource(GameRng::with_seed(0));
+        world.insert_resource(ExtraBalls::default());
+        world.insert_resource(Events::<BallServedEvent>::default());
+        world.insert_resource(Events::<BallBouncedEvent>::default());
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(add_extra_balls);
+
+        // The ball in play hasn't left the middle yet.
+        let ball = world
+            .spawn()
+            .insert_bundle((Ball, Position(Vec2::ZERO), Collider::cuboid(Vec2::splat(BALL_SIZE))))
+            .id();
+        world.resource_mut::<Events<BallBouncedEvent>>().send(BallBouncedEvent {
+            ball,
+            paddle: ball,
+            side: PlayerSide::Left,
+            speed: 0.0,
+            angle: 0.0,
+        });
+        stage.run(&mut world);
+
+        let mut positions: Vec<_> = world.query_filtered::<&Position, With<Ball>>().iter(&world).map(|position| position.0).collect();
+        assert_eq!(positions.len(), 2);
+        positions.retain(|&position| position != Vec2::ZERO);
+        assert_eq!(positions, vec![Vec2::Y * BALL_SIZE * 1.5]);
+    }
+}
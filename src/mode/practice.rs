@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    AppState,
+    assets::Assets,
+    game::{BALL_MAX_SPEED, GameState, MatchConfig, PlayerSide},
+    mode::{GameMode, GameModeAppExt, GoalOutcome},
+};
+
+/// How high up the best streak is shown.
+const BEST_HEIGHT: f32 = 230.0;
+
+/// One player against a wall, counting how many times in a row they return the ball. The score
+/// is the current streak, and missing the ball starts it again from nothing.
+pub struct PracticeModePlugin;
+
+impl Plugin for PracticeModePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_game_mode(PracticeMode)
+            .init_resource::<BestStreak>()
+            .add_system(update_best_streak
+                .run_in_state(AppState::InGame)
+                .run_if(is_practice));
+    }
+}
+
+pub struct PracticeMode;
+
+impl PracticeMode {
+    pub const ID: &'static str = "practice";
+}
+
+impl GameMode for PracticeMode {
+    fn id(&self) -> &'static str {
+        Self::ID
+    }
+
+    fn name(&self) -> &'static str {
+        "Practice"
+    }
+
+    fn description(&self) -> &'static str {
+        "Keep the ball going against the wall for as long as you can."
+    }
+
+    fn supports(&self, config: &MatchConfig) -> bool {
+        config.solo
+    }
+
+    fn settings_ui(&self, ui: &mut egui::Ui, config: &mut MatchConfig) {
+        ui.add(egui::Slider::new(&mut config.ball_speed, 100.0..=BALL_MAX_SPEED).step_by(50.0).text("Serve speed"));
+        ui.add(egui::Slider::new(&mut config.speed_increment, 0.0..=100.0).step_by(5.0).text("Speed gained per return"));
+    }
+
+    fn next_serve(&self, _config: &MatchConfig, _state: &GameState) -> PlayerSide {
+        PlayerSide::Left
+    }
+
+    fn paddle_hit(&self, side: PlayerSide, state: &mut GameState) {
+        let streak = &mut state.scores[side.index()];
+        *streak = streak.saturating_add(1);
+    }
+
+    fn goal(&self, side: PlayerSide, _config: &MatchConfig, state: &mut GameState) -> GoalOutcome {
+        state.scores[side.index()] = 0;
+        GoalOutcome::Missed
+    }
+
+    fn winner(&self, _config: &MatchConfig, _state: &GameState) -> Option<PlayerSide> {
+        None
+    }
+}
+
+fn is_practice(config: Res<MatchConfig>) -> bool {
+    config.game_mode == PracticeMode::ID
+}
+
+/// Longest streak of returns since the game was started, across every practice match.
+#[derive(Debug, Default)]
+pub struct BestStreak(pub u8);
+
+/// Shows the best streak.
+#[derive(Component)]
+struct BestStreakText;
+
+fn update_best_streak(
+    mut commands: Commands,
+    mut best: ResMut<BestStreak>,
+    assets: Res<Assets>,
+    game_state: Option<Res<GameState>>,
+    mut text_q: Query<&mut Text, With<BestStreakText>>,
+) {
+    let game_state = match game_state {
+        Some(game_state) => game_state,
+        None => return,
+    };
+    best.0 = best.0.max(game_state.score(PlayerSide::Left));
+    let value = format!("Best: {}", best.0);
+
+    match text_q.get_single_mut() {
+        Ok(mut text) => {
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
+        }
+        // The text goes away with the rest of the match's, so show a new one each match.
+        Err(_) => {
+            let style = TextStyle {
+                font: assets.font.clone(),
+                font_size: 40.0,
+                color: Color::WHITE,
+            };
+            let alignment = TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            };
+            commands
+                .spawn_bundle(Text2dBundle {
+                    text: Text::with_section(value, style, alignment),
+                    transform: Transform::from_xyz(0.0, BEST_HEIGHT, 0.0),
+                    ..default()
+                })
+                .insert(BestStreakText);
+        }
+    }
+}
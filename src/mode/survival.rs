@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use crate::{
+    game::{ALL_SIDES, GameState, MatchConfig, PlayerSide},
+    mode::{GameMode, GameModeAppExt, GoalOutcome},
+};
+
+/// Every side starts with a few lives and loses one for each goal it lets in. Sides with none
+/// left are walled off, and the last one standing wins.
+pub struct SurvivalModePlugin;
+
+impl Plugin for SurvivalModePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_game_mode(SurvivalMode);
+    }
+}
+
+pub struct SurvivalMode;
+
+impl SurvivalMode {
+    pub const ID: &'static str = "survival";
+}
+
+impl GameMode for SurvivalMode {
+    fn id(&self) -> &'static str {
+        Self::ID
+    }
+
+    fn name(&self) -> &'static str {
+        "Survival"
+    }
+
+    fn description(&self) -> &'static str {
+        "Every goal let in costs a life. The last side with any left wins."
+    }
+
+    fn supports(&self, config: &MatchConfig) -> bool {
+        !config.solo
+    }
+
+    fn counts_lives(&self) -> bool {
+        true
+    }
+
+    fn settings_ui(&self, ui: &mut egui::Ui, config: &mut MatchConfig) {
+        ui.add(egui::Slider::new(&mut config.lives, 1..=9).text("Lives"));
+    }
+
+    fn setup(&self, config: &MatchConfig, state: &mut GameState) {
+        for &side in config.sides() {
+            state.scores[side.index()] = config.lives;
+        }
+    }
+
+    /// Serves go around every side still in.
+    fn next_serve(&self, _config: &MatchConfig, state: &GameState) -> PlayerSide {
+        let start = ALL_SIDES.iter().position(|&side| side == state.serve).unwrap_or(0);
+        (1..=ALL_SIDES.len())
+            .map(|offset| ALL_SIDES[(start + offset) % ALL_SIDES.len()])
+            .find(|&side| state.score(side) > 0)
+            .unwrap_or(state.serve)
+    }
+
+    fn goal(&self, _side: PlayerSide, _config: &MatchConfig, _state: &mut GameState) -> GoalOutcome {
+        GoalOutcome::LifeLost
+    }
+
+    fn winner(&self, config: &MatchConfig, state: &GameState) -> Option<PlayerSide> {
+        let mut remaining = config.sides().iter().filter(|&&side| state.score(side) > 0);
+        match (remaining.next(), remaining.next()) {
+            (Some(&side), None) => Some(side),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    AppState,
+    assets::Assets,
+    game::{GameState, MatchConfig, PlayerSide},
+    mode::{GameMode, GameModeAppExt, GoalOutcome},
+    sim::TICK_RATE,
+};
+
+/// How high up the clock is shown, between the scores.
+const CLOCK_HEIGHT: f32 = 230.0;
+
+/// Points are scored like in a classic match, but whoever is ahead when the clock runs out wins.
+/// If it's a draw, the next goal wins.
+pub struct TimedModePlugin;
+
+impl Plugin for TimedModePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_game_mode(TimedMode)
+            .add_system(update_clock
+                .run_in_state(AppState::InGame)
+                .run_if(is_timed));
+    }
+}
+
+pub struct TimedMode;
+
+impl TimedMode {
+    pub const ID: &'static str = "timed";
+
+    /// Ticks of play left on the clock, or none once it has run out.
+    pub fn ticks_left(config: &MatchConfig, state: &GameState) -> u64 {
+        (config.time_limit as u64 * TICK_RATE as u64).saturating_sub(state.play_ticks)
+    }
+
+    /// Side with more points than any other, if there is one.
+    fn leader(config: &MatchConfig, state: &GameState) -> Option<PlayerSide> {
+        let sides = config.sides();
+        let best = sides.iter().map(|&side| state.score(side)).max()?;
+        let mut leaders = sides.iter().filter(|&&side| state.score(side) == best);
+        match (leaders.next(), leaders.next()) {
+            (Some(&side), None) => Some(side),
+            _ => None,
+        }
+    }
+}
+
+impl GameMode for TimedMode {
+    fn id(&self) -> &'static str {
+        Self::ID
+    }
+
+    fn name(&self) -> &'static str {
+        "Timed"
+    }
+
+    fn description(&self) -> &'static str {
+        "Most points when time runs out wins. A draw goes to sudden death."
+    }
+
+    fn supports(&self, config: &MatchConfig) -> bool {
+        !config.four_player && !config.solo
+    }
+
+    fn settings_ui(&self, ui: &mut egui::Ui, config: &mut MatchConfig) {
+        ui.add(egui::Slider::new(&mut config.time_limit, 30..=600).step_by(30.0).text("Seconds"));
+    }
+
+    fn goal(&self, side: PlayerSide, _config: &MatchConfig, _state: &mut GameState) -> GoalOutcome {
+        GoalOutcome::Point(side.opposite())
+    }
+
+    fn winner(&self, config: &MatchConfig, state: &GameState) -> Option<PlayerSide> {
+        if Self::ticks_left(config, state) > 0 {
+            return None;
+        }
+        Self::leader(config, state)
+    }
+}
+
+fn is_timed(config: Res<MatchConfig>) -> bool {
+    config.game_mode == TimedMode::ID
+}
+
+/// Shows the time left, or that the match is in sudden death.
+#[derive(Component)]
+struct ClockText;
+
+fn update_clock(
+    mut commands: Commands,
+    assets: Res<Assets>,
+    config: Res<MatchConfig>,
+    game_state: Option<Res<GameState>>,
+    mut clock_q: Query<&mut Text, With<ClockText>>,
+) {
+    let game_state = match game_state {
+        Some(game_state) => game_state,
+        None => return,
+    };
+    let ticks_left = TimedMode::ticks_left(&config, &game_state);
+    let value = if ticks_left > 0 {
+        let seconds = (ticks_left + TICK_RATE as u64 - 1) / TICK_RATE as u64;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        "Sudden death".into()
+    };
+
+    match clock_q.get_single_mut() {
+        Ok(mut text) => {
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
+        }
+        // The clock goes away with the rest of the match's text, so show a new one each match.
+        Err(_) => {
+            let style = TextStyle {
+                font: assets.font.clone(),
+                font_size: 40.0,
+                color: Color::WHITE,
+            };
+            let alignment = TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            };
+            commands
+                .spawn_bundle(Text2dBundle {
+                    text: Text::with_section(value, style, alignment),
+                    transform: Transform::from_xyz(0.0, CLOCK_HEIGHT, 0.0),
+                    ..default()
+                })
+                .insert(ClockText);
+        }
+    }
+}
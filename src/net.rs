@@ -0,0 +1,965 @@
+use std::{
+    any::Any,
+    collections::{hash_map::DefaultHasher, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    io,
+    net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use bevy::ecs::world::{EntityMut, EntityRef};
+use bevy::utils::Instant;
+use bevy_egui::{egui, EguiContext};
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState,
+    game::{Controller, GameRng, GameState, MatchConfig, MatchMode, Paddle, PaddleInput, PlayerSide, player_index},
+    mode::GameModes,
+    physics::Velocity,
+    profile::PlayerProfile,
+    sim::{Position, PreviousPosition, SimulationAppExt, SimulationClock, SimulationSchedule},
+};
+
+/// Port used for hosting when none is given.
+pub const DEFAULT_PORT: u16 = 7777;
+/// How many ticks after being pressed a local input takes effect. Gives it time to reach the
+/// other player, so that they rarely have to predict it.
+const INPUT_DELAY: usize = 2;
+/// Furthest the simulation may run ahead of the last confirmed remote input.
+const MAX_PREDICTION: u64 = 8;
+/// Most inputs sent in a single message.
+const MAX_INPUTS_PER_MESSAGE: usize = 64;
+/// Seconds between attempts to reach a host.
+const HELLO_INTERVAL: f32 = 0.25;
+/// Give up on the other player after not hearing from them for this many seconds.
+const TIMEOUT: f32 = 5.0;
+/// How many times to send the last inputs when leaving a match, in case some are lost.
+const FINAL_SENDS: usize = 5;
+/// Largest UDP payload, so that `Welcome` can carry the host's arena.
+const MAX_MESSAGE_SIZE: usize = 65_507;
+/// Extra delay for messages picked to arrive out of order by `NetConditions::reorder`.
+const REORDER_DELAY: Duration = Duration::from_millis(50);
+
+/// Two-player matches between machines on the same network. Both peers simulate the match,
+/// exchanging only their paddle inputs. The other player's input is predicted until it arrives,
+/// and the simulation is rolled back and run again from the first wrong prediction.
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<NetConditions>()
+            // How every simulated entity is placed and drawn. The rest is added by the plugins
+            // that simulate it.
+            .add_rollback_component::<Position>()
+            .add_rollback_component::<PreviousPosition>()
+            .add_rollback_component::<Transform>()
+            .add_rollback_component::<GlobalTransform>()
+            .add_rollback_component::<Sprite>()
+            .add_rollback_component::<Handle<Image>>()
+            .add_rollback_component::<Visibility>()
+            .add_rollback_component::<ComputedVisibility>()
+            // Runs before state transitions, so a predicted end of the match can be held back.
+            .add_stage_after(CoreStage::PreUpdate, NetStage, SystemStage::single_threaded())
+            .add_system_to_stage(NetStage, receive_and_rollback.exclusive_system())
+            .add_simulation_system(save_snapshot.exclusive_system().at_start())
+            .add_simulation_system(network_input
+                .run_in_state(AppState::InGame)
+                .run_if_resource_exists::<NetSession>()
+                .label("paddle_input"))
+            .add_system(send_inputs
+                .run_in_state(AppState::InGame)
+                .run_if_resource_exists::<NetSession>())
+            .add_system(poll_lobby
+                .run_in_state(AppState::MainMenu)
+                .run_if_resource_exists::<NetLobby>())
+            .add_system_to_stage(CoreStage::Last, update_connections)
+            .add_exit_system(AppState::InGame, end_session);
+    }
+}
+
+/// Panel for changing the simulated network conditions and watching the session, while hosting,
+/// joining or playing a network match. Needs `EguiPlugin`.
+pub struct NetDebugPlugin;
+
+impl Plugin for NetDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(net_debug_ui);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+struct NetStage;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Message {
+    /// Sent by a client until the host welcomes it, with the client player's profile.
+    Hello {
+        profile: Option<PlayerProfile>,
+    },
+    /// The host's reply to `Hello`, with the settings both play the match by and the host
+    /// player's profile.
+    Welcome {
+        config: Box<MatchConfig>,
+        profile: Option<PlayerProfile>,
+    },
+    /// The sender's paddle inputs from tick `start` on, and how many of the receiver's inputs
+    /// the sender has so far.
+    Input {
+        start: u64,
+        inputs: Vec<PaddleInput>,
+        ack: u64,
+    },
+    /// Checksums of the sender's confirmed state at the start of each tick from `start` on, and
+    /// how many of the receiver's checksums the sender has so far.
+    Checksums {
+        start: u64,
+        checksums: Vec<u64>,
+        ack: u64,
+    },
+}
+
+/// Bad network conditions to simulate on outgoing messages, for testing network matches on a
+/// single machine. Set from the command line or the network debug panel.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct NetConditions {
+    /// Delay added to every message, in milliseconds.
+    pub latency: u32,
+    /// Most extra random delay added to a message, in milliseconds.
+    pub jitter: u32,
+    /// Percentage of messages dropped.
+    pub loss: f32,
+    /// Percentage of messages sent twice.
+    pub duplicate: f32,
+    /// Percentage of messages held back long enough to arrive after later ones.
+    pub reorder: f32,
+}
+
+impl NetConditions {
+    fn chance(percentage: f32) -> bool {
+        fastrand::f32() * 100.0 < percentage
+    }
+
+    /// How long to hold back a message, or `None` to drop it.
+    fn delay(&self) -> Option<Duration> {
+        if Self::chance(self.loss) {
+            return None;
+        }
+        let mut delay = Duration::from_millis(self.latency as u64 + fastrand::u64(0..=self.jitter as u64));
+        if Self::chance(self.reorder) {
+            delay += REORDER_DELAY;
+        }
+        Some(delay)
+    }
+}
+
+/// Non-blocking UDP socket that sends and receives `Message`s.
+struct Connection {
+    socket: UdpSocket,
+    conditions: NetConditions,
+    /// Messages held back by `conditions`, with when to send them.
+    delayed: Vec<(Instant, SocketAddr, Vec<u8>)>,
+}
+
+impl Connection {
+    fn bind(address: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, conditions: default(), delayed: Vec::new() })
+    }
+
+    /// Another handle to the same socket, taking over any delayed messages.
+    fn take(&mut self) -> io::Result<Self> {
+        Ok(Self {
+            socket: self.socket.try_clone()?,
+            conditions: self.conditions,
+            delayed: std::mem::take(&mut self.delayed),
+        })
+    }
+
+    fn send(&mut self, to: SocketAddr, message: &Message) {
+        let bytes = serde_json::to_vec(message).expect("Failed to serialize message");
+        if self.conditions == NetConditions::default() {
+            self.send_now(to, &bytes);
+            return;
+        }
+
+        let copies = if NetConditions::chance(self.conditions.duplicate) { 2 } else { 1 };
+        for _ in 0..copies {
+            if let Some(delay) = self.conditions.delay() {
+                self.delayed.push((Instant::now() + delay, to, bytes.clone()));
+            }
+        }
+    }
+
+    fn send_now(&self, to: SocketAddr, bytes: &[u8]) {
+        if let Err(e) = self.socket.send_to(bytes, to) {
+            debug!("Failed to send to {}: {}", to, e);
+        }
+    }
+
+    /// Send the delayed messages that are due, or all of them if `all` is set.
+    fn flush(&mut self, all: bool) {
+        let now = Instant::now();
+        self.delayed.sort_by_key(|(send_at, ..)| *send_at);
+        let due = self.delayed
+            .iter()
+            .take_while(|(send_at, ..)| all || *send_at <= now)
+            .count();
+        for (_, to, bytes) in self.delayed.drain(..due).collect::<Vec<_>>() {
+            self.send_now(to, &bytes);
+        }
+    }
+
+    /// The next message waiting to be read, if any.
+    fn receive(&self) -> Option<(SocketAddr, Message)> {
+        let mut buffer = [0; MAX_MESSAGE_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, from)) => match serde_json::from_slice(&buffer[..length]) {
+                    Ok(message) => return Some((from, message)),
+                    Err(e) => warn!("Ignoring bad message from {}: {}", from, e),
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return None,
+                // Reported on some platforms after sending to a peer that isn't listening yet.
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => {
+                    warn!("Failed to receive: {}", e);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Waiting in the main menu for a network match to start.
+pub struct NetLobby {
+    connection: Connection,
+    pub role: LobbyRole,
+    /// Seconds since the last `Hello` was sent to the host.
+    since_hello: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LobbyRole {
+    /// Waiting for a player to join on this port.
+    Hosting {
+        port: u16,
+    },
+    /// Trying to reach the player hosting at this address.
+    Joining {
+        host: SocketAddr,
+    },
+}
+
+impl NetLobby {
+    /// Wait for a player to join on the given port.
+    pub fn host(port: u16) -> io::Result<Self> {
+        let connection = Connection::bind(SocketAddr::new([0, 0, 0, 0].into(), port))?;
+        Ok(Self { connection, role: LobbyRole::Hosting { port }, since_hello: 0.0 })
+    }
+
+    /// Try to join a player hosting at `address`, given as `host:port`.
+    pub fn join(address: &str) -> io::Result<Self> {
+        let host = address
+            .to_socket_addrs()?
+            .find(SocketAddr::is_ipv4)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No IPv4 address found for {}", address)))?;
+        let connection = Connection::bind(SocketAddr::new([0, 0, 0, 0].into(), 0))?;
+        Ok(Self { connection, role: LobbyRole::Joining { host }, since_hello: HELLO_INTERVAL })
+    }
+}
+
+/// Why the last network match ended early, for the menu to show.
+pub struct NetError(pub String);
+
+/// The network match in progress.
+pub struct NetSession {
+    connection: Connection,
+    peer: SocketAddr,
+    /// Side the local player controls. The host plays on the left.
+    pub local_side: PlayerSide,
+    /// Settings the match is played by, as sent in `Welcome`.
+    rules: MatchConfig,
+    /// Profile of the local player, sent to the peer.
+    profile: Option<PlayerProfile>,
+    /// Match settings from before the session started, restored when it ends.
+    previous_config: MatchConfig,
+    /// Local inputs for each tick, sampled `INPUT_DELAY` ticks ahead of the simulation.
+    local_inputs: Vec<PaddleInput>,
+    /// Remote inputs received so far for each tick.
+    remote_inputs: Vec<PaddleInput>,
+    /// Remote inputs the simulation used for each tick so far, whether received or predicted.
+    used_remote_inputs: Vec<PaddleInput>,
+    /// How many of the local inputs the peer has received.
+    remote_ack: u64,
+    /// State at the start of each recent tick, to roll back to.
+    snapshots: VecDeque<Snapshot>,
+    /// Earliest tick that was simulated with a wrong prediction.
+    rollback_to: Option<u64>,
+    /// State change the simulation asked for, held back until all inputs leading up to it are
+    /// confirmed, since it may have been caused by a wrong prediction.
+    pending_state: Option<AppState>,
+    /// Seconds since anything was received from the peer.
+    since_heard: f32,
+    /// Checksums of the local state at the start of each tick, once all inputs before it are
+    /// confirmed.
+    local_checksums: Vec<u64>,
+    /// Checksums of the peer's confirmed state received so far.
+    remote_checksums: Vec<u64>,
+    /// How many of the local checksums the peer has received.
+    remote_checksum_ack: u64,
+    /// First tick where the local and remote states didn't match.
+    desync_tick: Option<u64>,
+    rollbacks: u32,
+    longest_rollback: u64,
+}
+
+impl NetSession {
+    fn new(connection: Connection, peer: SocketAddr, local_side: PlayerSide, rules: MatchConfig, profile: Option<PlayerProfile>, previous_config: MatchConfig) -> Self {
+        Self {
+            connection,
+            peer,
+            local_side,
+            rules,
+            profile,
+            previous_config,
+            local_inputs: vec![PaddleInput::default(); INPUT_DELAY],
+            remote_inputs: Vec::new(),
+            used_remote_inputs: Vec::new(),
+            remote_ack: 0,
+            snapshots: VecDeque::new(),
+            rollback_to: None,
+            pending_state: None,
+            since_heard: 0.0,
+            local_checksums: Vec::new(),
+            remote_checksums: Vec::new(),
+            remote_checksum_ack: 0,
+            desync_tick: None,
+            rollbacks: 0,
+            longest_rollback: 0,
+        }
+    }
+
+    fn receive(&mut self, delta: f32) {
+        self.since_heard += delta;
+        while let Some((from, message)) = self.connection.receive() {
+            if from != self.peer {
+                continue;
+            }
+            self.since_heard = 0.0;
+            match message {
+                // The client didn't get our welcome.
+                Message::Hello { .. } if self.local_side == PlayerSide::Left => {
+                    let welcome = Message::Welcome { config: Box::new(self.rules.clone()), profile: self.profile.clone() };
+                    self.connection.send(self.peer, &welcome);
+                }
+                Message::Input { start, inputs, ack } => self.receive_inputs(start, inputs, ack),
+                Message::Checksums { start, checksums, ack } => self.receive_checksums(start, checksums, ack),
+                _ => {}
+            }
+        }
+    }
+
+    fn receive_inputs(&mut self, start: u64, inputs: Vec<PaddleInput>, ack: u64) {
+        self.remote_ack = self.remote_ack.max(ack);
+        let start = start as usize;
+        if start > self.remote_inputs.len() {
+            // Missing some inputs in between. They'll be sent again.
+            return;
+        }
+        for (tick, input) in (start..).zip(inputs).skip(self.remote_inputs.len() - start) {
+            if matches!(self.used_remote_inputs.get(tick), Some(used) if *used != input) {
+                let tick = tick as u64;
+                self.rollback_to = Some(self.rollback_to.map_or(tick, |rollback_to| rollback_to.min(tick)));
+            }
+            self.remote_inputs.push(input);
+        }
+    }
+
+    fn receive_checksums(&mut self, start: u64, checksums: Vec<u64>, ack: u64) {
+        self.remote_checksum_ack = self.remote_checksum_ack.max(ack);
+        let start = start as usize;
+        if start > self.remote_checksums.len() {
+            return;
+        }
+        let new = checksums.into_iter().skip(self.remote_checksums.len() - start);
+        self.remote_checksums.extend(new);
+        self.check_desync();
+    }
+
+    /// Compare the checksums of every tick both peers have confirmed.
+    fn check_desync(&mut self) {
+        if self.desync_tick.is_some() {
+            return;
+        }
+        let desync = self.local_checksums
+            .iter()
+            .zip(&self.remote_checksums)
+            .position(|(local, remote)| local != remote);
+        if let Some(tick) = desync {
+            error!("Desync with {} at tick {}", self.peer, tick);
+            self.desync_tick = Some(tick as u64);
+        }
+    }
+
+    fn send_inputs(&mut self) {
+        let start = (self.remote_ack as usize).min(self.local_inputs.len());
+        let end = (start + MAX_INPUTS_PER_MESSAGE).min(self.local_inputs.len());
+        let message = Message::Input {
+            start: start as u64,
+            inputs: self.local_inputs[start..end].to_vec(),
+            ack: self.remote_inputs.len() as u64,
+        };
+        self.connection.send(self.peer, &message);
+
+        let start = (self.remote_checksum_ack as usize).min(self.local_checksums.len());
+        let end = (start + MAX_INPUTS_PER_MESSAGE).min(self.local_checksums.len());
+        let message = Message::Checksums {
+            start: start as u64,
+            checksums: self.local_checksums[start..end].to_vec(),
+            ack: self.remote_checksums.len() as u64,
+        };
+        self.connection.send(self.peer, &message);
+    }
+
+    /// Record the checksums of ticks whose state no longer depends on any predicted input.
+    fn confirm_checksums(&mut self) {
+        let confirmed = self.remote_inputs.len() as u64;
+        let next = self.local_checksums.len() as u64;
+        let checksums: Vec<_> = self.snapshots
+            .iter()
+            .filter(|snapshot| snapshot.tick >= next && snapshot.tick <= confirmed)
+            .map(|snapshot| (snapshot.tick, snapshot.checksum))
+            .collect();
+        for (tick, checksum) in checksums {
+            if tick != self.local_checksums.len() as u64 {
+                warn!("Missing the state of tick {} to check for desyncs", self.local_checksums.len());
+                return;
+            }
+            self.local_checksums.push(checksum);
+        }
+        self.check_desync();
+    }
+}
+
+/// A component or resource saved in a snapshot.
+type Saved = Box<dyn Any + Send + Sync>;
+
+/// Saves and restores one type of component on every simulated entity.
+struct RollbackComponent {
+    save: fn(&EntityRef) -> Option<Saved>,
+    restore: fn(&mut EntityMut, Option<&Saved>),
+}
+
+/// Saves and restores one resource the simulation changes.
+struct RollbackResource {
+    save: fn(&World) -> Option<Saved>,
+    restore: fn(&mut World, Option<&Saved>),
+}
+
+/// Everything that goes in a snapshot, added by the plugins that simulate it.
+#[derive(Default)]
+struct RollbackRegistry {
+    components: Vec<RollbackComponent>,
+    resources: Vec<RollbackResource>,
+}
+
+pub trait RollbackAppExt {
+    /// Save a component of simulated entities in every snapshot, to put back when rolling back a
+    /// network match.
+    fn add_rollback_component<T: Component + Clone>(&mut self) -> &mut Self;
+
+    /// Save a resource the simulation changes in every snapshot, to put back when rolling back a
+    /// network match.
+    fn add_rollback_resource<T: Clone + Send + Sync + 'static>(&mut self) -> &mut Self;
+}
+
+impl RollbackAppExt for App {
+    fn add_rollback_component<T: Component + Clone>(&mut self) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(RollbackRegistry::default)
+            .components
+            .push(RollbackComponent {
+                save: |entity| entity.get::<T>().map(|component| Box::new(component.clone()) as Saved),
+                restore: |entity, saved| match saved.and_then(|saved| saved.downcast_ref::<T>()) {
+                    Some(component) => {
+                        entity.insert(component.clone());
+                    }
+                    None => {
+                        entity.remove::<T>();
+                    }
+                },
+            });
+        self
+    }
+
+    fn add_rollback_resource<T: Clone + Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(RollbackRegistry::default)
+            .resources
+            .push(RollbackResource {
+                save: |world| world.get_resource::<T>().map(|resource| Box::new(resource.clone()) as Saved),
+                restore: |world, saved| match saved.and_then(|saved| saved.downcast_ref::<T>()) {
+                    Some(resource) => world.insert_resource(resource.clone()),
+                    None => {
+                        world.remove_resource::<T>();
+                    }
+                },
+            });
+        self
+    }
+}
+
+/// Everything the simulation changes, as of the start of a tick. Simulated entities are the ones
+/// with a `Position`.
+struct Snapshot {
+    tick: u64,
+    /// Hash of the state that matters to the outcome, to compare with the other player's.
+    checksum: u64,
+    /// Each registered resource, in the order they were added.
+    resources: Vec<Option<Saved>>,
+    /// Each simulated entity, with its registered components.
+    entities: Vec<(Entity, Vec<Option<Saved>>)>,
+}
+
+impl Snapshot {
+    fn save(world: &mut World, registry: &RollbackRegistry) -> Self {
+        let game_state = world.resource::<GameState>();
+        let tick = game_state.tick;
+        let mut hasher = DefaultHasher::new();
+        (tick, game_state.scores, game_state.serve, world.resource::<GameRng>()).hash(&mut hasher);
+
+        // Entities are spawned in the same order on both machines, but their IDs may differ, and
+        // ones put back by a rollback end up in a different order.
+        let mut query = world.query::<(Entity, &Position, Option<&Velocity>, Option<&PaddleInput>)>();
+        let mut entity_hashes: Vec<_> = query
+            .iter(world)
+            .map(|(_, position, velocity, input)| {
+                let mut hasher = DefaultHasher::new();
+                position.0.x.to_bits().hash(&mut hasher);
+                position.0.y.to_bits().hash(&mut hasher);
+                if let Some(velocity) = velocity {
+                    velocity.linear.x.to_bits().hash(&mut hasher);
+                    velocity.linear.y.to_bits().hash(&mut hasher);
+                }
+                input.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect();
+        entity_hashes.sort_unstable();
+        entity_hashes.hash(&mut hasher);
+
+        let entities = query
+            .iter(world)
+            .map(|(entity, ..)| {
+                let entity_ref = world.entity(entity);
+                let components = registry.components.iter().map(|component| (component.save)(&entity_ref)).collect();
+                (entity, components)
+            })
+            .collect();
+        Self {
+            tick,
+            checksum: hasher.finish(),
+            resources: registry.resources.iter().map(|resource| (resource.save)(world)).collect(),
+            entities,
+        }
+    }
+
+    /// Put the world back as it was. Entities spawned since are despawned, and ones despawned
+    /// since are spawned again with new IDs. Paddles are the only entities referred to by ID from
+    /// one tick to the next, and they're never despawned in a two-player match.
+    fn restore(&mut self, world: &mut World, registry: &RollbackRegistry) {
+        for (resource, saved) in registry.resources.iter().zip(&self.resources) {
+            (resource.restore)(world, saved.as_ref());
+        }
+
+        let saved: HashSet<_> = self.entities.iter().map(|(entity, _)| *entity).collect();
+        let spawned: Vec<_> = world
+            .query_filtered::<Entity, With<Position>>()
+            .iter(world)
+            .filter(|entity| !saved.contains(entity))
+            .collect();
+        for entity in spawned {
+            world.entity_mut(entity).despawn_recursive();
+        }
+
+        for (entity, components) in &mut self.entities {
+            let mut entity_mut = match world.get_entity_mut(*entity) {
+                Some(entity_mut) => entity_mut,
+                None => world.spawn(),
+            };
+            *entity = entity_mut.id();
+            for (component, saved) in registry.components.iter().zip(components.iter()) {
+                (component.restore)(&mut entity_mut, saved.as_ref());
+            }
+        }
+    }
+}
+
+/// Local IP address other machines on the network can most likely reach this one at.
+pub fn local_ip() -> Option<IpAddr> {
+    // Connecting a UDP socket doesn't send anything, but picks the interface to send from.
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    socket.local_addr().ok().map(|address| address.ip())
+}
+
+fn poll_lobby(
+    mut commands: Commands,
+    mut lobby: ResMut<NetLobby>,
+    mut config: ResMut<MatchConfig>,
+    modes: Res<GameModes>,
+    time: Res<Time>,
+) {
+    let NetLobby { connection, role, since_hello } = &mut *lobby;
+    // Whoever plays on this machine is player 1.
+    let profile = config.players.first().cloned().flatten();
+    let (peer, local_side, rules, remote_profile) = match *role {
+        LobbyRole::Hosting { .. } => {
+            let hello = std::iter::from_fn(|| connection.receive())
+                .find_map(|(from, message)| match message {
+                    Message::Hello { profile } => Some((from, profile)),
+                    _ => None,
+                });
+            let (client, remote_profile) = match hello {
+                Some(hello) => hello,
+                None => return,
+            };
+            // Both play by the host's settings, with one player on each machine.
+            let mut rules = MatchConfig {
+                seed: Some(config.seed.unwrap_or_else(|| fastrand::u64(..))),
+                team_size: 1,
+                four_player: false,
+                solo: false,
+                players: Vec::new(),
+                ..config.clone()
+            };
+            modes.pick_playable(&mut rules);
+            connection.send(client, &Message::Welcome { config: Box::new(rules.clone()), profile: profile.clone() });
+            info!("{} joined", client);
+            (client, PlayerSide::Left, rules, remote_profile)
+        }
+        LobbyRole::Joining { host } => {
+            *since_hello += time.delta_seconds();
+            if *since_hello >= HELLO_INTERVAL {
+                *since_hello = 0.0;
+                connection.send(host, &Message::Hello { profile: profile.clone() });
+            }
+            let welcome = std::iter::from_fn(|| connection.receive())
+                .find_map(|(from, message)| match message {
+                    Message::Welcome { config, profile } if from == host => Some((*config, profile)),
+                    _ => None,
+                });
+            let (rules, remote_profile) = match welcome {
+                Some(welcome) => welcome,
+                None => return,
+            };
+            info!("Joined {}", host);
+            (host, PlayerSide::Right, rules, remote_profile)
+        }
+    };
+
+    let connection = match connection.take() {
+        Ok(connection) => connection,
+        Err(e) => {
+            commands.insert_resource(NetError(format!("Failed to start the match: {}", e)));
+            commands.remove_resource::<NetLobby>();
+            return;
+        }
+    };
+    let previous_config = config.clone();
+    *config = MatchConfig {
+        mode: MatchMode::Network,
+        players: match local_side {
+            PlayerSide::Left => vec![profile.clone(), remote_profile],
+            _ => vec![remote_profile, profile.clone()],
+        },
+        ..rules.clone()
+    };
+    commands.insert_resource(NetSession::new(connection, peer, local_side, rules, profile, previous_config));
+    commands.remove_resource::<NetLobby>();
+    commands.insert_resource(NextState(AppState::InGame));
+}
+
+fn save_snapshot(world: &mut World) {
+    if !world.contains_resource::<NetSession>() || !world.contains_resource::<GameState>() {
+        return;
+    }
+    let snapshot = world.resource_scope(|world, registry: Mut<RollbackRegistry>| Snapshot::save(world, &registry));
+    let mut session = world.resource_mut::<NetSession>();
+    // Replace any snapshots from a timeline that was rolled back.
+    while matches!(session.snapshots.back(), Some(last) if last.tick >= snapshot.tick) {
+        session.snapshots.pop_back();
+    }
+    session.snapshots.push_back(snapshot);
+    while session.snapshots.len() > MAX_PREDICTION as usize + 1 {
+        session.snapshots.pop_front();
+    }
+}
+
+fn network_input(
+    keys: Res<Input<KeyCode>>,
+    mut session: ResMut<NetSession>,
+    game_state: Res<GameState>,
+    config: Res<MatchConfig>,
+    mut input_q: Query<(&Paddle, &Controller, &mut PaddleInput)>,
+) {
+    let tick = game_state.tick as usize;
+
+    // Sample the keyboard the first time each tick is simulated, for use a few ticks later.
+    if session.local_inputs.len() == tick + INPUT_DELAY {
+        // Without a profile, either of the usual pairs of keys will do.
+        let (up, down, action) = match config.profile(player_index(session.local_side, 0)) {
+            Some(profile) => {
+                let (up, down) = profile.controls.keys();
+                (keys.pressed(up), keys.pressed(down), keys.pressed(profile.controls.action_key()))
+            }
+            None => (
+                keys.any_pressed([KeyCode::W, KeyCode::Up]),
+                keys.any_pressed([KeyCode::S, KeyCode::Down]),
+                keys.any_pressed([KeyCode::LShift, KeyCode::RShift]),
+            ),
+        };
+        session.local_inputs.push(PaddleInput { movement: up as i8 - down as i8, action });
+    }
+    let local = session.local_inputs[tick];
+
+    // Until the remote input arrives, predict that the other player is still doing the same.
+    let remote = session.remote_inputs
+        .get(tick)
+        .or_else(|| session.remote_inputs.last())
+        .copied()
+        .unwrap_or_default();
+    session.used_remote_inputs.truncate(tick);
+    session.used_remote_inputs.push(remote);
+
+    for (paddle, controller, mut input) in input_q.iter_mut() {
+        if *controller == Controller::Network {
+            *input = if paddle.side == session.local_side { local } else { remote };
+        }
+    }
+}
+
+fn receive_and_rollback(world: &mut World) {
+    let delta = world.resource::<Time>().delta_seconds();
+    let mut session = match world.get_resource_mut::<NetSession>() {
+        Some(session) => session,
+        None => return,
+    };
+    session.receive(delta);
+    if session.since_heard > TIMEOUT {
+        warn!("Lost connection to {}", session.peer);
+        world.insert_resource(NetError("Lost connection to the other player.".into()));
+        world.insert_resource(NextState(AppState::MainMenu));
+        return;
+    }
+    let rollback_to = session.rollback_to.take();
+    let game_tick = match world.get_resource::<GameState>() {
+        Some(game_state) => game_state.tick,
+        None => return,
+    };
+
+    if let Some(next) = world.remove_resource::<NextState<AppState>>() {
+        world.resource_mut::<NetSession>().pending_state = Some(next.0);
+    }
+    if let Some(rollback_to) = rollback_to {
+        rollback(world, rollback_to, game_tick);
+    }
+
+    world.resource_mut::<NetSession>().confirm_checksums();
+
+    let game_tick = world.resource::<GameState>().tick;
+    let confirmed = world.resource::<NetSession>().remote_inputs.len() as u64;
+    let pending_state = world.resource::<NetSession>().pending_state;
+    let clock_tick = world.resource::<SimulationClock>().tick;
+    let tick_limit = match pending_state {
+        Some(state) if game_tick <= confirmed => {
+            world.resource_mut::<NetSession>().pending_state = None;
+            world.insert_resource(NextState(state));
+            None
+        }
+        // Wait for the inputs that would confirm it.
+        Some(_) => Some(clock_tick),
+        None => Some(clock_tick + (confirmed + MAX_PREDICTION).saturating_sub(game_tick)),
+    };
+    world.resource_mut::<SimulationClock>().tick_limit = tick_limit;
+}
+
+/// Restore the state from the start of tick `to`, then simulate again up to tick `until` with the
+/// inputs received since.
+fn rollback(world: &mut World, to: u64, until: u64) {
+    let mut snapshots = std::mem::take(&mut world.resource_mut::<NetSession>().snapshots);
+    match snapshots.iter_mut().find(|snapshot| snapshot.tick == to) {
+        Some(snapshot) => world.resource_scope(|world, registry: Mut<RollbackRegistry>| snapshot.restore(world, &registry)),
+        None => {
+            error!("No snapshot to roll back to tick {}", to);
+            world.resource_mut::<NetSession>().snapshots = snapshots;
+            return;
+        }
+    }
+    debug!("Rolling back {} ticks", until - to);
+    let mut session = world.resource_mut::<NetSession>();
+    session.snapshots = snapshots;
+    session.pending_state = None;
+    session.rollbacks += 1;
+    session.longest_rollback = session.longest_rollback.max(until - to);
+
+    world.resource_mut::<SimulationClock>().resimulating = true;
+    world.resource_scope(|world, mut schedule: Mut<SimulationSchedule>| {
+        while world.resource::<GameState>().tick < until && !world.contains_resource::<NextState<AppState>>() {
+            schedule.run_tick(world);
+        }
+    });
+    world.resource_mut::<SimulationClock>().resimulating = false;
+    if let Some(next) = world.remove_resource::<NextState<AppState>>() {
+        world.resource_mut::<NetSession>().pending_state = Some(next.0);
+    }
+}
+
+fn send_inputs(
+    mut session: ResMut<NetSession>,
+) {
+    session.send_inputs();
+}
+
+/// Apply the latest network conditions, and send any delayed messages that are due.
+fn update_connections(
+    conditions: Res<NetConditions>,
+    session: Option<ResMut<NetSession>>,
+    lobby: Option<ResMut<NetLobby>>,
+) {
+    let connections = session
+        .map(|session| &mut session.into_inner().connection)
+        .into_iter()
+        .chain(lobby.map(|lobby| &mut lobby.into_inner().connection));
+    for connection in connections {
+        connection.conditions = *conditions;
+        connection.flush(false);
+    }
+}
+
+fn end_session(
+    mut commands: Commands,
+    session: Option<ResMut<NetSession>>,
+    mut clock: ResMut<SimulationClock>,
+) {
+    if let Some(mut session) = session {
+        // The other player may still need our last inputs to confirm the end of the match.
+        for _ in 0..FINAL_SENDS {
+            session.send_inputs();
+        }
+        session.connection.flush(true);
+        // Deferred, so that other systems leaving the match still see the network match's config.
+        commands.insert_resource(session.previous_config.clone());
+        commands.remove_resource::<NetSession>();
+    }
+    clock.tick_limit = None;
+}
+
+fn net_debug_ui(
+    mut ctx: ResMut<EguiContext>,
+    mut conditions: ResMut<NetConditions>,
+    session: Option<Res<NetSession>>,
+    lobby: Option<Res<NetLobby>>,
+    game_state: Option<Res<GameState>>,
+) {
+    if session.is_none() && lobby.is_none() {
+        return;
+    }
+
+    let window = egui::Window::new("Network")
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0]);
+    window.show(ctx.ctx_mut(), |ui| {
+        egui::CollapsingHeader::new("Simulated conditions").show(ui, |ui| {
+            // Only mark the resource as changed when a slider actually moves.
+            let mut edited = *conditions;
+            ui.add(egui::Slider::new(&mut edited.latency, 0..=500).text("Latency (ms)"));
+            ui.add(egui::Slider::new(&mut edited.jitter, 0..=200).text("Jitter (ms)"));
+            ui.add(egui::Slider::new(&mut edited.loss, 0.0..=100.0).text("Loss (%)"));
+            ui.add(egui::Slider::new(&mut edited.duplicate, 0.0..=100.0).text("Duplicate (%)"));
+            ui.add(egui::Slider::new(&mut edited.reorder, 0.0..=100.0).text("Reorder (%)"));
+            if edited != *conditions {
+                *conditions = edited;
+            }
+        });
+
+        if let (Some(session), Some(game_state)) = (session, game_state) {
+            ui.separator();
+            ui.label(format!("Peer: {}", session.peer));
+            ui.label(format!("Tick: {} ({} confirmed)", game_state.tick, session.remote_inputs.len()));
+            ui.label(format!("Rollbacks: {} (longest {} ticks)", session.rollbacks, session.longest_rollback));
+            match session.desync_tick {
+                Some(tick) => ui.colored_label(egui::Color32::RED, format!("Desynced at tick {}", tick)),
+                None => ui.label(format!("In sync ({} ticks checked)", session.local_checksums.len().min(session.remote_checksums.len()))),
+            };
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app
+            .add_rollback_resource::<GameState>()
+            .add_rollback_resource::<GameRng>()
+            .add_rollback_component::<Position>()
+            .add_rollback_component::<Velocity>()
+            .insert_resource(GameState::new(0, Vec::new(), PlayerSide::Left))
+            .insert_resource(GameRng::with_seed(0));
+        app
+    }
+
+    fn save(world: &mut World) -> Snapshot {
+        world.resource_scope(|world, registry: Mut<RollbackRegistry>| Snapshot::save(world, &registry))
+    }
+
+    fn restore(world: &mut World, snapshot: &mut Snapshot) {
+        world.resource_scope(|world, registry: Mut<RollbackRegistry>| snapshot.restore(world, &registry));
+    }
+
+    #[test]
+    fn restore_puts_components_back() {
+        let mut app = app();
+        let world = &mut app.world;
+        let ball = world.spawn().insert(Position(Vec2::ZERO)).insert(Velocity::from_linear(Vec2::X)).id();
+        let mut snapshot = save(world);
+
+        world.entity_mut(ball).insert(Position(Vec2::ONE)).remove::<Velocity>();
+        world.resource_mut::<GameState>().tick = 5;
+        restore(world, &mut snapshot);
+
+        assert_eq!(world.get::<Position>(ball).unwrap().0, Vec2::ZERO);
+        assert_eq!(world.get::<Velocity>(ball).unwrap().linear, Vec2::X);
+        assert_eq!(world.resource::<GameState>().tick, 0);
+        assert_eq!(save(world).checksum, snapshot.checksum);
+    }
+
+    #[test]
+    fn restore_spawns_and_despawns() {
+        let mut app = app();
+        let world = &mut app.world;
+        let brick = world.spawn().insert(Position(Vec2::ONE)).id();
+        let mut snapshot = save(world);
+
+        // A ball broke the brick, then another came into play.
+        world.despawn(brick);
+        let extra_ball = world.spawn().insert(Position(Vec2::ZERO)).id();
+        restore(world, &mut snapshot);
+
+        assert!(world.get_entity(extra_ball).is_none());
+        let positions: Vec<_> = world.query::<&Position>().iter(world).map(|position| position.0).collect();
+        assert_eq!(positions, vec![Vec2::ONE]);
+
+        // Rolling back to the same tick again keeps the brick that was put back.
+        restore(world, &mut snapshot);
+        assert_eq!(world.query::<&Position>().iter(world).count(), 1);
+    }
+}
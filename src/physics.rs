@@ -0,0 +1,389 @@
+use bevy::prelude::*;
+
+use crate::{
+    net::RollbackAppExt,
+    sim::{Position, PreviousPosition, SimulationAppExt, TICK_DELTA},
+};
+
+/// Minimal, deterministic 2D physics for the simulation. Moving bodies are integrated and checked
+/// for overlaps once per tick, and gameplay systems decide how to respond to each collision.
+///
+/// This stands in for heron, which steps rapier on its own clock in its own stage. Here all of
+/// the physics state is plain components stepped by the simulation schedule, so a tick gives the
+/// same result on every machine and netplay can snapshot and roll it back.
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<CollisionEvent>()
+            .add_rollback_component::<Velocity>()
+            .add_rollback_component::<Collider>()
+            .add_rollback_component::<Sensor>()
+            .add_simulation_system(integrate_velocity.label(PhysicsSystem::Integrate))
+            .add_simulation_system(
+                detect_collisions
+                    .label(PhysicsSystem::DetectCollisions)
+                    .after(PhysicsSystem::Integrate)
+            );
+
+        // Stands in for heron's `debug-2d` renderer, which the feature used to turn on.
+        #[cfg(feature = "phys-viz")]
+        app.add_system(debug::spawn_collider_sprites);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum PhysicsSystem {
+    Integrate,
+    DetectCollisions,
+}
+
+/// Linear velocity of a moving body, in pixels per second. Only bodies with a velocity are checked
+/// for collisions against other colliders.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Velocity {
+    pub linear: Vec2,
+}
+
+impl Velocity {
+    pub fn from_linear(linear: Vec2) -> Self {
+        Self { linear }
+    }
+}
+
+/// Box collision shape, centered on the entity's `Position` and turned by `rotation`.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Collider {
+    pub half_extents: Vec2,
+    /// Anticlockwise rotation, in radians.
+    pub rotation: f32,
+}
+
+impl Collider {
+    pub fn cuboid(size: Vec2) -> Self {
+        Self { half_extents: size / 2.0, rotation: 0.0 }
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> Self {
+        Self { rotation, ..self }
+    }
+
+    /// Directions of the box's width and height.
+    fn axes(&self) -> [Vec2; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        [Vec2::new(cos, sin), Vec2::new(-sin, cos)]
+    }
+
+    /// Half the length of the box's shadow on an axis.
+    fn radius_along(&self, axis: Vec2) -> f32 {
+        let [x, y] = self.axes();
+        self.half_extents.x * x.dot(axis).abs() + self.half_extents.y * y.dot(axis).abs()
+    }
+}
+
+/// Marks a collider that bodies pass through rather than bounce off, like heron's
+/// `RigidBody::Sensor`.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Sensor;
+
+/// Sent when a moving body starts overlapping another collider, like heron's
+/// `CollisionEvent::Started`. For a collider that isn't a `Sensor`, it's sent again on every tick
+/// the body is still heading into it, in case something pushed it back in after it bounced.
+#[derive(Debug, Clone, Copy)]
+pub struct CollisionEvent {
+    /// The moving body.
+    pub entity: Entity,
+    /// The collider it overlaps.
+    pub other: Entity,
+    /// Unit vector pointing out of `other` at the point of contact, along which `entity` can be
+    /// moved to separate them.
+    pub normal: Vec2,
+    /// How far `entity` has to move along `normal` to stop overlapping `other`.
+    pub depth: f32,
+}
+
+/// Find the overlap between two boxes, returning the contact normal (pointing out of `b` toward
+/// `a`) and penetration depth if they intersect.
+///
+/// The normal is that of the face of `b` that `a`'s center is in front of. When it's past two
+/// faces at once, `a` has hit a corner, and the normal points from the corner to `a`'s center as
+/// if the corner were rounded.
+pub fn box_overlap(a_pos: Vec2, a: &Collider, b_pos: Vec2, b: &Collider) -> Option<(Vec2, f32)> {
+    let delta = a_pos - b_pos;
+    let [b_x, b_y] = b.axes();
+
+    // Separating axis test: the boxes overlap unless their shadows are apart on one of their axes.
+    let [a_x, a_y] = a.axes();
+    for axis in [a_x, a_y, b_x, b_y] {
+        if a.radius_along(axis) + b.radius_along(axis) - delta.dot(axis).abs() <= 0.0 {
+            return None;
+        }
+    }
+
+    // Work out the contact from where `a` is relative to `b`'s faces.
+    let local = Vec2::new(delta.dot(b_x), delta.dot(b_y));
+    let past_x = local.x.abs() > b.half_extents.x;
+    let past_y = local.y.abs() > b.half_extents.y;
+    if past_x && past_y {
+        let corner = Vec2::new(b.half_extents.x.copysign(local.x), b.half_extents.y.copysign(local.y));
+        let offset = (local - corner).normalize();
+        let normal = b_x * offset.x + b_y * offset.y;
+        let depth = (a.radius_along(normal) - (local - corner).length()).max(0.0);
+        return Some((normal, depth));
+    }
+    let overlap_x = a.radius_along(b_x) + b.half_extents.x - local.x.abs();
+    let overlap_y = a.radius_along(b_y) + b.half_extents.y - local.y.abs();
+    // Inside `b`, separate along the axis with the least penetration.
+    if past_x || (!past_y && overlap_x < overlap_y) {
+        Some((b_x * local.x.signum(), overlap_x))
+    } else {
+        Some((b_y * local.y.signum(), overlap_y))
+    }
+}
+
+/// Bounce a velocity off a surface with the given normal. A velocity already leaving the surface
+/// is left alone, so touching two pieces of the same wall in one tick doesn't turn the ball back
+/// into it.
+pub fn reflect(velocity: Vec2, normal: Vec2) -> Vec2 {
+    let along_normal = velocity.dot(normal);
+    if along_normal < 0.0 {
+        velocity - 2.0 * along_normal * normal
+    } else {
+        velocity
+    }
+}
+
+fn integrate_velocity(
+    mut body_q: Query<(&mut Position, &Velocity)>,
+) {
+    for (mut position, velocity) in body_q.iter_mut() {
+        position.0 += velocity.linear * TICK_DELTA;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn detect_collisions(
+    mut collisions: EventWriter<CollisionEvent>,
+    body_q: Query<(Entity, &Position, Option<&PreviousPosition>, &Collider, &Velocity)>,
+    collider_q: Query<(Entity, &Position, Option<&PreviousPosition>, &Collider, Option<&Velocity>, Option<&Sensor>)>,
+) {
+    for (entity, position, previous, collider, velocity) in body_q.iter() {
+        for (other, other_position, other_previous, other_collider, other_velocity, sensor) in collider_q.iter() {
+            if entity == other {
+                continue;
+            }
+            let (normal, depth) = match box_overlap(position.0, collider, other_position.0, other_collider) {
+                Some(overlap) => overlap,
+                None => continue,
+            };
+
+            // Positions are stored after gameplay systems have pushed bodies out of whatever they
+            // bounced off, so these were only touching already if they still were after that.
+            let previous = previous.map_or(position.0, |previous| previous.0);
+            let other_previous = other_previous.map_or(other_position.0, |previous| previous.0);
+            let started = box_overlap(previous, collider, other_previous, other_collider).is_none();
+            let closing = (velocity.linear - other_velocity.map_or(Vec2::ZERO, |v| v.linear)).dot(normal) < 0.0;
+            if started || (sensor.is_none() && closing) {
+                collisions.send(CollisionEvent { entity, other, normal, depth });
+            }
+        }
+    }
+}
+
+#[cfg(feature = "phys-viz")]
+mod debug {
+    use bevy::prelude::*;
+
+    use super::Collider;
+
+    /// Draw a translucent box over every collider, including invisible ones like goals.
+    pub fn spawn_collider_sprites(
+        mut commands: Commands,
+        collider_q: Query<(Entity, &Collider), Added<Collider>>,
+    ) {
+        for (entity, collider) in collider_q.iter() {
+            let sprite = commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba(0.0, 1.0, 0.0, 0.3),
+                        custom_size: Some(collider.half_extents * 2.0),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    ..default()
+                })
+                .id();
+            commands.entity(entity).add_child(sprite);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use bevy::ecs::event::Events;
+
+    use super::*;
+
+    fn ball() -> Collider {
+        Collider::cuboid(Vec2::splat(10.0))
+    }
+
+    fn assert_close(actual: Vec2, expected: Vec2) {
+        assert!((actual - expected).length() < 1e-4, "expected {}, got {}", expected, actual);
+    }
+
+    /// Bounce a ball at a position off a wall, the way `ball_wall_bounce` does.
+    fn bounce(position: Vec2, velocity: Vec2, wall_position: Vec2, wall: &Collider) -> (Vec2, Vec2) {
+        let (normal, depth) = box_overlap(position, &ball(), wall_position, wall).expect("Ball should touch the wall");
+        (position + normal * depth, reflect(velocity, normal))
+    }
+
+    /// Step collision detection once, the way the simulation does after moving `ball` to `y`, and
+    /// count the collisions it reports.
+    fn detect_at(world: &mut World, stage: &mut SystemStage, ball: Entity, y: f32) -> usize {
+        let position = world.get::<Position>(ball).unwrap().0;
+        world.get_mut::<PreviousPosition>(ball).unwrap().0 = position;
+        world.get_mut::<Position>(ball).unwrap().0.y = y;
+        stage.run(world);
+        let mut events = world.resource_mut::<Events<CollisionEvent>>();
+        let count = events.iter_current_update_events().count();
+        events.update();
+        count
+    }
+
+    /// A world with a collider at the origin and a ball above it, heading down.
+    fn collision_world(collider: impl Bundle) -> (World, SystemStage, Entity) {
+        let mut world = World::new();
+        world.init_resource::<Events<CollisionEvent>>();
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(detect_collisions);
+        world.spawn().insert_bundle(collider);
+        let ball = world
+            .spawn()
+            .insert_bundle((
+                Position(Vec2::new(0.0, 20.0)),
+                PreviousPosition::default(),
+                ball(),
+                Velocity::from_linear(Vec2::new(0.0, -60.0)),
+            ))
+            .id();
+        (world, stage, ball)
+    }
+
+    #[test]
+    fn sensor_collision_starts_once() {
+        let goal = (Position(Vec2::ZERO), Collider::cuboid(Vec2::new(100.0, 10.0)), Sensor);
+        let (mut world, mut stage, ball) = collision_world(goal);
+
+        // Staying in contact over several ticks only counts as one collision.
+        assert_eq!(detect_at(&mut world, &mut stage, ball, 8.0), 1);
+        assert_eq!(detect_at(&mut world, &mut stage, ball, 7.0), 0);
+
+        // Coming apart and touching again is a new one.
+        assert_eq!(detect_at(&mut world, &mut stage, ball, 20.0), 0);
+        assert_eq!(detect_at(&mut world, &mut stage, ball, 8.0), 1);
+    }
+
+    #[test]
+    fn solid_collision_repeats_while_closing() {
+        let wall = (Position(Vec2::ZERO), Collider::cuboid(Vec2::new(100.0, 10.0)));
+        let (mut world, mut stage, ball) = collision_world(wall);
+        assert_eq!(detect_at(&mut world, &mut stage, ball, 8.0), 1);
+        // Still heading into the wall, e.g. after being pushed back in by a paddle.
+        assert_eq!(detect_at(&mut world, &mut stage, ball, 7.0), 1);
+
+        // On its way out, staying in contact doesn't count again.
+        world.get_mut::<Velocity>(ball).unwrap().linear.y = 60.0;
+        assert_eq!(detect_at(&mut world, &mut stage, ball, 8.0), 0);
+    }
+
+    #[test]
+    fn apart() {
+        let wall = Collider::cuboid(Vec2::new(100.0, 10.0));
+        assert!(box_overlap(Vec2::new(0.0, 20.0), &ball(), Vec2::ZERO, &wall).is_none());
+        // Inside the bounding box of the rotated wall, but clear of the wall itself.
+        let rotated = wall.with_rotation(FRAC_PI_4);
+        assert!(box_overlap(Vec2::new(30.0, -30.0), &ball(), Vec2::ZERO, &rotated).is_none());
+    }
+
+    #[test]
+    fn horizontal_wall() {
+        let wall = Collider::cuboid(Vec2::new(100.0, 10.0));
+        let (position, velocity) = bounce(Vec2::new(10.0, 8.0), Vec2::new(3.0, -4.0), Vec2::ZERO, &wall);
+        assert_close(position, Vec2::new(10.0, 10.0));
+        assert_close(velocity, Vec2::new(3.0, 4.0));
+    }
+
+    #[test]
+    fn vertical_wall() {
+        let wall = Collider::cuboid(Vec2::new(10.0, 100.0));
+        let (position, velocity) = bounce(Vec2::new(-8.0, 10.0), Vec2::new(4.0, 3.0), Vec2::ZERO, &wall);
+        assert_close(position, Vec2::new(-10.0, 10.0));
+        assert_close(velocity, Vec2::new(-4.0, 3.0));
+    }
+
+    #[test]
+    fn angled_wall() {
+        // A wall running diagonally up and to the right sends a ball falling onto it off to the side.
+        let wall = Collider::cuboid(Vec2::new(200.0, 10.0)).with_rotation(FRAC_PI_4);
+        let normal = Vec2::new(-1.0, 1.0).normalize();
+        let (position, velocity) = bounce(normal * 9.0, Vec2::new(0.0, -10.0), Vec2::ZERO, &wall);
+        assert_close(velocity, Vec2::new(-10.0, 0.0));
+        assert!(box_overlap(position, &ball(), Vec2::ZERO, &wall).map_or(true, |(_, depth)| depth < 1e-4));
+    }
+
+    #[test]
+    fn rotated_wall() {
+        let angle = 30.0_f32.to_radians();
+        let wall = Collider::cuboid(Vec2::new(200.0, 10.0)).with_rotation(angle);
+        let normal = Vec2::new(-angle.sin(), angle.cos());
+        let velocity = Vec2::new(2.0, -5.0);
+        let (_, bounced) = bounce(normal * 10.0, velocity, Vec2::ZERO, &wall);
+        // Speed along the wall is kept and speed into it reversed.
+        let along = normal.perp();
+        assert!((bounced.dot(along) - velocity.dot(along)).abs() < 1e-4);
+        assert!((bounced.dot(normal) + velocity.dot(normal)).abs() < 1e-4);
+        assert!((bounced.length() - velocity.length()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn convex_corner() {
+        // Clipping the corner of a block head on glances off at an angle.
+        let block = Collider::cuboid(Vec2::new(20.0, 20.0));
+        let (normal, depth) = box_overlap(Vec2::new(13.0, 13.0), &ball(), Vec2::ZERO, &block).unwrap();
+        assert_close(normal, Vec2::new(1.0, 1.0).normalize());
+        assert!(depth > 0.0);
+        let (_, velocity) = bounce(Vec2::new(13.0, 13.0), Vec2::new(-10.0, 0.0), Vec2::ZERO, &block);
+        assert_close(velocity, Vec2::new(0.0, 10.0));
+    }
+
+    #[test]
+    fn concave_corner() {
+        // Running into where two walls meet sends the ball back the way it came.
+        let floor = Collider::cuboid(Vec2::new(100.0, 10.0));
+        let side = Collider::cuboid(Vec2::new(10.0, 100.0));
+        let (floor_position, side_position) = (Vec2::new(0.0, -10.0), Vec2::new(10.0, 0.0));
+        let (ball_position, mut velocity) = (Vec2::new(1.0, -1.0), Vec2::new(5.0, -5.0));
+        for (position, wall) in [(floor_position, &floor), (side_position, &side)] {
+            let (normal, _) = box_overlap(ball_position, &ball(), position, wall).unwrap();
+            velocity = reflect(velocity, normal);
+        }
+        assert_close(velocity, Vec2::new(-5.0, 5.0));
+    }
+
+    #[test]
+    fn wall_seam() {
+        // Touching two pieces of one flat wall in the same tick only bounces once.
+        let piece = Collider::cuboid(Vec2::new(50.0, 10.0));
+        let ball_position = Vec2::new(0.0, 8.0);
+        let mut velocity = Vec2::new(3.0, -4.0);
+        for position in [Vec2::new(-25.0, 0.0), Vec2::new(25.0, 0.0)] {
+            let (normal, _) = box_overlap(ball_position, &ball(), position, &piece).unwrap();
+            velocity = reflect(velocity, normal);
+        }
+        assert_close(velocity, Vec2::new(3.0, 4.0));
+    }
+}
@@ -0,0 +1,639 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState,
+    assets::Assets,
+    game::{
+        BALL_MAX_SPEED, Ball, BallBouncedEvent, GameRng, Held, LastHit, MatchConfig, Paddle, PlayerSide, ServeCountdown,
+        Serving, SpeedScale, WallBundle, score_position,
+    },
+    mode::breakout::BrickBrokenEvent,
+    net::RollbackAppExt,
+    physics::{Collider, CollisionEvent, Sensor, Velocity},
+    sim::{Position, SimulationAppExt, TICK_RATE},
+};
+
+/// Size of a power-up waiting to be claimed.
+const POWER_UP_SIZE: f32 = 30.0;
+/// Furthest from the middle of the arena power-ups appear on their own.
+const SPAWN_AREA: (f32, f32) = (200.0, 200.0);
+/// Tries at finding a clear spot for a power-up before giving up until the next one is due.
+const SPAWN_TRIES: usize = 5;
+/// The power-ups in `assets/power-ups.json`, played with unless others are picked.
+static BUILT_IN: Lazy<PowerUpSettings> = Lazy::new(|| {
+    serde_json::from_str(include_str!("../assets/power-ups.json")).expect("Built-in power-ups should be valid")
+});
+
+/// Power-ups that appear in the arena, on a timer or dropped by bricks, and are claimed by
+/// whoever last hit the ball that touches them.
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<PowerUpClaimedEvent>()
+            .add_rollback_resource::<ActivePowerUps>()
+            .add_rollback_component::<PowerUp>()
+            .add_rollback_component::<PowerUpWall>()
+            .add_enter_system(AppState::InGame, reset_power_ups)
+            .add_simulation_system(claim_power_ups
+                .run_in_state(AppState::InGame)
+                .label("claim_power_ups")
+                .after("ball_paddle_bounce")
+                .before("check_scored"))
+            .add_simulation_system(stick_balls
+                .run_in_state(AppState::InGame)
+                .after("claim_power_ups"))
+            .add_simulation_system(update_power_ups
+                .run_in_state(AppState::InGame)
+                .label("update_power_ups")
+                .after("claim_power_ups")
+                .before("check_match_over"))
+            .add_simulation_system(spawn_power_ups
+                .run_in_state(AppState::InGame)
+                .after("update_power_ups")
+                .before("check_match_over"))
+            .add_system(label_power_ups.run_in_state(AppState::InGame))
+            .add_system(show_power_ups.run_in_state(AppState::InGame));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    /// The claimer's paddles grow by `magnitude`.
+    BigPaddle,
+    /// Everyone else's paddles shrink by `magnitude`.
+    SmallPaddle,
+    /// Every ball in play speeds up by `magnitude` while it lasts.
+    FastBall,
+    /// Every ball in play slows down by `magnitude` while it lasts.
+    SlowBall,
+    /// A wall closes off the claimer's goal.
+    ExtraWall,
+    /// The claimer's paddles hold onto the ball for `magnitude` seconds before letting it go.
+    StickyPaddle,
+    /// Everyone else's paddles move the opposite way to their controls.
+    ReversedControls,
+}
+
+impl PowerUpKind {
+    /// Shown on the power-up, and next to the scores while it lasts.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::BigPaddle => "Big",
+            Self::SmallPaddle => "Small",
+            Self::FastBall => "Fast",
+            Self::SlowBall => "Slow",
+            Self::ExtraWall => "Wall",
+            Self::StickyPaddle => "Sticky",
+            Self::ReversedControls => "Reversed",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            Self::BigPaddle => Color::rgb(0.3, 0.8, 0.3),
+            Self::SmallPaddle => Color::rgb(0.8, 0.3, 0.3),
+            Self::FastBall => Color::rgb(0.9, 0.6, 0.2),
+            Self::SlowBall => Color::rgb(0.3, 0.6, 0.9),
+            Self::ExtraWall => Color::rgb(0.7, 0.7, 0.7),
+            Self::StickyPaddle => Color::rgb(0.8, 0.8, 0.2),
+            Self::ReversedControls => Color::rgb(0.7, 0.3, 0.8),
+        }
+    }
+
+    /// Sides the power-up works on, when claimed by a side. `None` for the balls themselves.
+    fn targets(&self, claimer: PlayerSide, sides: &[PlayerSide]) -> Vec<Option<PlayerSide>> {
+        match self {
+            Self::BigPaddle | Self::ExtraWall | Self::StickyPaddle => vec![Some(claimer)],
+            Self::SmallPaddle | Self::ReversedControls => {
+                sides.iter().filter(|&&side| side != claimer).map(|&side| Some(side)).collect()
+            }
+            Self::FastBall | Self::SlowBall => vec![None],
+        }
+    }
+}
+
+/// What claiming a power-up does when the same one is still working on a side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stacking {
+    /// Start the one already working over, from its full duration.
+    Refresh,
+    /// Add the duration onto what's left of the one already working.
+    Extend,
+    /// Work alongside it, up to `max_stacks` at once. Past that, the one closest to wearing off
+    /// is started over instead.
+    Stack,
+}
+
+/// How one kind of power-up behaves.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PowerUpConfig {
+    pub kind: PowerUpKind,
+    /// How likely this kind is to be picked, relative to the others.
+    pub weight: u32,
+    /// Seconds the power-up works for.
+    pub duration: f32,
+    /// How strong it is, as described by each kind.
+    pub magnitude: f32,
+    pub stacking: Stacking,
+    #[serde(default = "default_max_stacks")]
+    pub max_stacks: u8,
+}
+
+fn default_max_stacks() -> u8 {
+    1
+}
+
+impl PowerUpConfig {
+    fn duration_ticks(&self) -> u32 {
+        (self.duration * TICK_RATE as f32).round() as u32
+    }
+}
+
+/// When power-ups appear and what each kind does.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PowerUpSettings {
+    /// Seconds between power-ups appearing in the arena. None appear on their own when 0, though
+    /// bricks can still drop them.
+    pub interval: u32,
+    /// Seconds a power-up waits in the arena to be claimed before disappearing.
+    pub lifetime: u32,
+    /// Most power-ups waiting in the arena at once, not counting ones dropped by bricks.
+    pub max_waiting: u8,
+    pub table: Vec<PowerUpConfig>,
+}
+
+impl Default for PowerUpSettings {
+    fn default() -> Self {
+        BUILT_IN.clone()
+    }
+}
+
+impl PowerUpSettings {
+    pub fn get(&self, kind: PowerUpKind) -> Option<&PowerUpConfig> {
+        self.table.iter().find(|config| config.kind == kind)
+    }
+
+    /// Pick a kind of power-up at random, going by their weights.
+    fn pick(&self, rng: &mut GameRng) -> Option<PowerUpKind> {
+        let total: u32 = self.table.iter().map(|config| config.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.usize(0..total as usize) as u32;
+        for config in &self.table {
+            if roll < config.weight {
+                return Some(config.kind);
+            }
+            roll -= config.weight;
+        }
+        None
+    }
+}
+
+/// Sent when a side claims a power-up.
+pub struct PowerUpClaimedEvent {
+    pub kind: PowerUpKind,
+    pub side: PlayerSide,
+}
+
+/// Power-up waiting in the arena to be claimed.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    /// Ticks until it disappears.
+    pub ticks_left: u32,
+    /// Whether it appeared on its own, rather than being dropped by a brick.
+    spawned: bool,
+}
+
+/// Claimed power-up that's still working.
+#[derive(Debug, Clone, Copy)]
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    /// Side it works on, or none for the balls.
+    pub side: Option<PlayerSide>,
+    pub magnitude: f32,
+    pub ticks_left: u32,
+}
+
+/// Power-ups working in the match being played.
+#[derive(Debug, Default, Clone)]
+pub struct ActivePowerUps {
+    pub effects: Vec<ActiveEffect>,
+    /// Ticks since a power-up last appeared on its own.
+    ticks_since_spawn: u32,
+}
+
+impl ActivePowerUps {
+    /// Everything of one kind working on a side.
+    fn on(&self, kind: PowerUpKind, side: Option<PlayerSide>) -> impl Iterator<Item = &ActiveEffect> {
+        self.effects.iter().filter(move |effect| effect.kind == kind && effect.side == side)
+    }
+
+    /// How much the balls' speed should be scaled by, from everything working on them.
+    pub fn ball_speed_scale(&self) -> f32 {
+        self.on(PowerUpKind::FastBall, None)
+            .chain(self.on(PowerUpKind::SlowBall, None))
+            .map(|effect| effect.magnitude)
+            .product()
+    }
+
+    /// Set a claimed power-up working on a side, going by its stacking rule.
+    fn add(&mut self, config: &PowerUpConfig, side: Option<PlayerSide>) {
+        let ticks = config.duration_ticks();
+        let existing = self.on(config.kind, side).count();
+        let same = self.effects.iter_mut().filter(|effect| effect.kind == config.kind && effect.side == side);
+        match config.stacking {
+            _ if existing == 0 => {}
+            Stacking::Refresh => {
+                for effect in same {
+                    effect.ticks_left = ticks;
+                }
+                return;
+            }
+            Stacking::Extend => {
+                for effect in same {
+                    effect.ticks_left += ticks;
+                }
+                return;
+            }
+            Stacking::Stack if existing >= config.max_stacks.max(1) as usize => {
+                if let Some(effect) = same.min_by_key(|effect| effect.ticks_left) {
+                    effect.ticks_left = ticks;
+                }
+                return;
+            }
+            Stacking::Stack => {}
+        }
+        self.effects.push(ActiveEffect { kind: config.kind, side, magnitude: config.magnitude, ticks_left: ticks });
+    }
+}
+
+/// Wall across a side's goal, put up by a power-up.
+#[derive(Component, Clone, Copy)]
+struct PowerUpWall {
+    side: PlayerSide,
+}
+
+/// Lists the power-ups working on a side.
+#[derive(Component)]
+struct PowerUpText {
+    side: PlayerSide,
+}
+
+fn reset_power_ups(mut commands: Commands) {
+    commands.insert_resource(ActivePowerUps::default());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn claim_power_ups(
+    mut commands: Commands,
+    mut events: EventReader<CollisionEvent>,
+    mut claimed: EventWriter<PowerUpClaimedEvent>,
+    mut active: ResMut<ActivePowerUps>,
+    config: Res<MatchConfig>,
+    last_hit_q: Query<&LastHit>,
+    mut power_up_q: Query<&mut PowerUp>,
+) {
+    for event in events.iter() {
+        let (side, mut power_up) = match (last_hit_q.get(event.entity), power_up_q.get_mut(event.other)) {
+            (Ok(&LastHit(Some(side))), Ok(power_up)) => (side, power_up),
+            _ => continue,
+        };
+        // Already claimed by another ball this tick.
+        if power_up.ticks_left == 0 {
+            continue;
+        }
+        power_up.ticks_left = 0;
+        commands.entity(event.other).despawn_recursive();
+
+        let kind = power_up.kind;
+        claimed.send(PowerUpClaimedEvent { kind, side });
+        let power_up_config = match config.power_ups.get(kind) {
+            Some(power_up_config) => *power_up_config,
+            None => continue,
+        };
+        for target in kind.targets(side, config.sides()) {
+            active.add(&power_up_config, target);
+        }
+    }
+}
+
+/// Catch balls returned by sticky paddles.
+fn stick_balls(
+    mut commands: Commands,
+    mut bounced: EventReader<BallBouncedEvent>,
+    active: Res<ActivePowerUps>,
+    ball_q: Query<(&Position, &Velocity)>,
+    paddle_q: Query<&Position>,
+) {
+    for event in bounced.iter() {
+        let seconds = match active.on(PowerUpKind::StickyPaddle, Some(event.side)).next() {
+            Some(effect) => effect.magnitude,
+            None => continue,
+        };
+        if let (Ok((position, velocity)), Ok(paddle_position)) = (ball_q.get(event.ball), paddle_q.get(event.paddle)) {
+            commands.entity(event.ball).insert(Held {
+                paddle: event.paddle,
+                offset: position.0 - paddle_position.0,
+                velocity: velocity.linear,
+                ticks_left: (seconds * TICK_RATE as f32).round() as u32,
+            });
+        }
+    }
+}
+
+/// Wear power-ups down, and keep the balls, paddles and walls in line with the ones still
+/// working.
+#[allow(clippy::type_complexity)]
+fn update_power_ups(
+    mut commands: Commands,
+    mut active: ResMut<ActivePowerUps>,
+    config: Res<MatchConfig>,
+    mut power_up_q: Query<(Entity, &mut PowerUp)>,
+    mut ball_q: Query<
+        (&mut Velocity, &mut SpeedScale, Option<&mut Held>),
+        (With<Ball>, Without<Serving>, Without<ServeCountdown>),
+    >,
+    mut paddle_q: Query<(&mut Paddle, &mut Collider, &mut Sprite)>,
+    wall_q: Query<(Entity, &PowerUpWall)>,
+) {
+    for (entity, mut power_up) in power_up_q.iter_mut() {
+        power_up.ticks_left = power_up.ticks_left.saturating_sub(1);
+        if power_up.ticks_left == 0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for effect in &mut active.effects {
+        effect.ticks_left = effect.ticks_left.saturating_sub(1);
+    }
+    active.effects.retain(|effect| effect.ticks_left > 0);
+
+    // Balls waiting to be served pick up the scale once they're on their way.
+    let speed_scale = active.ball_speed_scale();
+    for (mut velocity, mut scale, held) in ball_q.iter_mut() {
+        if scale.0 == speed_scale {
+            continue;
+        }
+        let factor = speed_scale / scale.0;
+        velocity.linear = (velocity.linear * factor).clamp_length_max(BALL_MAX_SPEED);
+        if let Some(mut held) = held {
+            held.velocity = (held.velocity * factor).clamp_length_max(BALL_MAX_SPEED);
+        }
+        scale.0 = speed_scale;
+    }
+
+    for (mut paddle, mut collider, mut sprite) in paddle_q.iter_mut() {
+        let side = Some(paddle.side);
+        let scale: f32 = active.on(PowerUpKind::BigPaddle, side)
+            .chain(active.on(PowerUpKind::SmallPaddle, side))
+            .map(|effect| effect.magnitude)
+            .product();
+        let length = (paddle.base_length * scale).min(paddle.lane_length);
+        if paddle.length != length {
+            paddle.length = length;
+            collider.half_extents = paddle.size() / 2.0;
+            sprite.custom_size = Some(paddle.size());
+        }
+        let reversed = active.on(PowerUpKind::ReversedControls, side).next().is_some();
+        if paddle.reversed != reversed {
+            paddle.reversed = reversed;
+        }
+    }
+
+    for &side in config.sides() {
+        let walled = active.on(PowerUpKind::ExtraWall, Some(side)).next().is_some();
+        let wall = wall_q.iter().find(|(_, wall)| wall.side == side).map(|(entity, _)| entity);
+        match (walled, wall, config.arena.goal(side)) {
+            (true, None, Some(goal)) => {
+                commands
+                    .spawn_bundle(WallBundle::new(&goal.wall(), PowerUpKind::ExtraWall.color()))
+                    .insert(PowerUpWall { side });
+            }
+            (false, Some(wall), _) => commands.entity(wall).despawn_recursive(),
+            _ => {}
+        }
+    }
+}
+
+fn spawn_power_ups(
+    mut commands: Commands,
+    mut broken: EventReader<BrickBrokenEvent>,
+    mut active: ResMut<ActivePowerUps>,
+    mut rng: ResMut<GameRng>,
+    config: Res<MatchConfig>,
+    power_up_q: Query<&PowerUp>,
+) {
+    let settings = &config.power_ups;
+    let lifetime = settings.lifetime * TICK_RATE;
+    for event in broken.iter().filter(|event| event.power_up) {
+        if let Some(kind) = settings.pick(&mut rng) {
+            spawn_power_up(&mut commands, PowerUp { kind, ticks_left: lifetime, spawned: false }, event.position);
+        }
+    }
+
+    if settings.interval == 0 {
+        return;
+    }
+    active.ticks_since_spawn += 1;
+    if active.ticks_since_spawn < settings.interval * TICK_RATE {
+        return;
+    }
+    active.ticks_since_spawn = 0;
+    if power_up_q.iter().filter(|power_up| power_up.spawned).count() >= settings.max_waiting as usize {
+        return;
+    }
+
+    // Somewhere clear of the walls, obstacles and the middle the ball is served from.
+    let arena = &config.arena;
+    let clear = |point: Vec2| {
+        point.length() > POWER_UP_SIZE * 2.0
+            && !arena.walls.iter().chain(&arena.obstacles).any(|rect| rect.contains(point))
+    };
+    for _ in 0..SPAWN_TRIES {
+        let point = Vec2::new(rng.f32() * 2.0 - 1.0, rng.f32() * 2.0 - 1.0) * Vec2::from(SPAWN_AREA);
+        if !clear(point) {
+            continue;
+        }
+        if let Some(kind) = settings.pick(&mut rng) {
+            spawn_power_up(&mut commands, PowerUp { kind, ticks_left: lifetime, spawned: true }, point);
+        }
+        break;
+    }
+}
+
+fn spawn_power_up(commands: &mut Commands, power_up: PowerUp, position: Vec2) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: power_up.kind.color(),
+                custom_size: Some(Vec2::splat(POWER_UP_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_translation(position.extend(0.5)),
+            ..default()
+        })
+        .insert(power_up)
+        .insert(Position(position))
+        .insert(Collider::cuboid(Vec2::splat(POWER_UP_SIZE)))
+        .insert(Sensor);
+}
+
+/// Write what each new power-up does on it, including ones put back by a rollback.
+fn label_power_ups(
+    mut commands: Commands,
+    assets: Res<Assets>,
+    power_up_q: Query<(Entity, &PowerUp), Added<PowerUp>>,
+) {
+    let style = TextStyle {
+        font: assets.font.clone(),
+        font_size: 14.0,
+        color: Color::BLACK,
+    };
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+    for (entity, power_up) in power_up_q.iter() {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn_bundle(Text2dBundle {
+                text: Text::with_section(power_up.kind.label(), style.clone(), alignment),
+                transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                ..default()
+            });
+        });
+    }
+}
+
+/// List what's working on each side, with the seconds left, under its score.
+fn show_power_ups(
+    mut commands: Commands,
+    assets: Res<Assets>,
+    config: Res<MatchConfig>,
+    active: Option<Res<ActivePowerUps>>,
+    mut text_q: Query<(&mut Text, &PowerUpText)>,
+) {
+    let active = match active {
+        Some(active) => active,
+        None => return,
+    };
+    let describe = |side: PlayerSide| {
+        let effects = active.effects.iter().filter(|effect| effect.side.map_or(true, |target| target == side));
+        let labels: Vec<_> = effects
+            .map(|effect| format!("{} {}s", effect.kind.label(), (effect.ticks_left + TICK_RATE - 1) / TICK_RATE))
+            .collect();
+        labels.join("\n")
+    };
+
+    if !text_q.is_empty() {
+        for (mut text, power_up_text) in text_q.iter_mut() {
+            let value = describe(power_up_text.side);
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
+        }
+        return;
+    }
+    // The text goes away with the rest of the match's, so show new ones each match.
+    let style = TextStyle {
+        font: assets.font.clone(),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Top,
+        horizontal: HorizontalAlign::Center,
+    };
+    for &side in config.sides() {
+        let position = score_position(side, &config) - Vec2::new(0.0, 140.0);
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(describe(side), style.clone(), alignment),
+                transform: Transform::from_translation(position.extend(0.0)),
+                ..default()
+            })
+            .insert(PowerUpText { side });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVE_SPEED: f32 = 400.0;
+
+    fn fast_ball(ticks_left: u32) -> ActiveEffect {
+        ActiveEffect { kind: PowerUpKind::FastBall, side: None, magnitude: 1.5, ticks_left }
+    }
+
+    fn spawn_ball(world: &mut World, speed: f32) -> Entity {
+        world
+            .spawn()
+            .insert_bundle((Ball, Velocity::from_linear(Vec2::new(speed, 0.0)), SpeedScale::default()))
+            .id()
+    }
+
+    fn speed(world: &World, ball: Entity) -> f32 {
+        world.get::<Velocity>(ball).unwrap().linear.length()
+    }
+
+    fn assert_speed(world: &World, ball: Entity, expected: f32) {
+        let actual = speed(world, ball);
+        assert!((actual - expected).abs() < 1e-3, "expected speed {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn ball_speed_wears_off() {
+        let mut world = World::new();
+        world.insert_resource(MatchConfig::default());
+        world.insert_resource(ActivePowerUps { effects: vec![fast_ball(2)], ..default() });
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(update_power_ups);
+
+        let claimed_with = spawn_ball(&mut world, SERVE_SPEED);
+        stage.run(&mut world);
+        assert_speed(&world, claimed_with, SERVE_SPEED * 1.5);
+
+        // Served after the claim, like after a goal, and held by a sticky paddle.
+        let served = spawn_ball(&mut world, SERVE_SPEED);
+        world.entity_mut(claimed_with).insert(Held {
+            paddle: served,
+            offset: Vec2::ZERO,
+            velocity: Vec2::new(SERVE_SPEED * 1.5, 0.0),
+            ticks_left: 10,
+        });
+        world.get_mut::<Velocity>(claimed_with).unwrap().linear = Vec2::ZERO;
+
+        // Once it's worn off, both are back to the speed they were served at.
+        stage.run(&mut world);
+        assert!(world.resource::<ActivePowerUps>().effects.is_empty());
+        assert_speed(&world, served, SERVE_SPEED);
+        assert!((world.get::<Held>(claimed_with).unwrap().velocity.length() - SERVE_SPEED).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ball_speed_skips_serves() {
+        let mut world = World::new();
+        world.insert_resource(MatchConfig::default());
+        world.insert_resource(ActivePowerUps { effects: vec![fast_ball(10)], ..default() });
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(update_power_ups);
+
+        // Waiting on a countdown, it's sped up once it's served instead.
+        let ball = spawn_ball(&mut world, 0.0);
+        world.entity_mut(ball).insert(ServeCountdown::new(3, Vec2::new(SERVE_SPEED, 0.0)));
+        stage.run(&mut world);
+        assert_eq!(world.get::<SpeedScale>(ball).unwrap().0, 1.0);
+
+        world.entity_mut(ball).remove::<ServeCountdown>();
+        world.get_mut::<Velocity>(ball).unwrap().linear = Vec2::new(SERVE_SPEED, 0.0);
+        stage.run(&mut world);
+        assert_speed(&world, ball, SERVE_SPEED * 1.5);
+    }
+}
@@ -0,0 +1,213 @@
+use std::{fs, io, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ALLOW_EXPORT;
+
+/// File player profiles are saved to, in the working directory.
+pub const PROFILES_FILE: &str = "profiles.json";
+
+/// Loads the player profiles picked from in the main menu.
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        let profiles = if ALLOW_EXPORT {
+            let mut profiles = Profiles::load(Path::new(PROFILES_FILE)).unwrap_or_else(|e| {
+                warn!("Failed to load player profiles from {}: {}", PROFILES_FILE, e);
+                Profiles::default()
+            });
+            // Saved straight away, so the match history keeps matching them to the same ids.
+            if profiles.assign_missing_ids() {
+                profiles.save_and_report();
+            }
+            profiles
+        } else {
+            Profiles::default()
+        };
+        app.insert_resource(profiles);
+    }
+}
+
+/// Pair of keys moving a paddle up and down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlScheme {
+    Ws,
+    Arrows,
+    Ik,
+    Numpad,
+}
+
+impl ControlScheme {
+    pub const ALL: [Self; 4] = [Self::Ws, Self::Arrows, Self::Ik, Self::Numpad];
+
+    /// Controls used by a player without a profile, different for each of the first four.
+    pub fn default_for(player: usize) -> Self {
+        Self::ALL[player % Self::ALL.len()]
+    }
+
+    /// Up and down keys.
+    pub fn keys(&self) -> (KeyCode, KeyCode) {
+        match self {
+            Self::Ws => (KeyCode::W, KeyCode::S),
+            Self::Arrows => (KeyCode::Up, KeyCode::Down),
+            Self::Ik => (KeyCode::I, KeyCode::K),
+            Self::Numpad => (KeyCode::Numpad8, KeyCode::Numpad5),
+        }
+    }
+
+    /// Right and left keys, for paddles on the top and bottom.
+    pub fn sideways_keys(&self) -> (KeyCode, KeyCode) {
+        match self {
+            Self::Ws => (KeyCode::D, KeyCode::A),
+            Self::Arrows => (KeyCode::Right, KeyCode::Left),
+            Self::Ik => (KeyCode::L, KeyCode::J),
+            Self::Numpad => (KeyCode::Numpad6, KeyCode::Numpad4),
+        }
+    }
+
+    /// Key for the paddle's action, like dashing.
+    pub fn action_key(&self) -> KeyCode {
+        match self {
+            Self::Ws => KeyCode::LShift,
+            Self::Arrows => KeyCode::RShift,
+            Self::Ik => KeyCode::U,
+            Self::Numpad => KeyCode::Numpad0,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Ws => "WASD",
+            Self::Arrows => "Arrows",
+            Self::Ik => "IJKL",
+            Self::Numpad => "Numpad 8456",
+        }
+    }
+}
+
+/// A named player, with how their paddle looks and how they like to control it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerProfile {
+    /// Identifies the profile in the match history, even after it's renamed. Profiles saved
+    /// before ids were given out have 0 until loaded.
+    #[serde(default)]
+    pub id: u64,
+    pub name: String,
+    /// Paddle colour, as sRGB.
+    pub color: [u8; 3],
+    pub controls: ControlScheme,
+}
+
+impl PlayerProfile {
+    pub fn new(name: String) -> Self {
+        Self {
+            id: fastrand::u64(1..),
+            name,
+            color: [255, 255, 255],
+            controls: ControlScheme::Ws,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        let [r, g, b] = self.color;
+        Color::rgb_u8(r, g, b)
+    }
+}
+
+/// Every profile created in the menu, and which ones are picked to play the next match.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Profiles {
+    pub profiles: Vec<PlayerProfile>,
+    /// Indices of the profiles picked for each player, in the order given by
+    /// `game::player_index`. Nobody's picked when not set.
+    #[serde(default)]
+    pub selected: Vec<Option<usize>>,
+}
+
+impl Profiles {
+    /// Load the profiles, or start with none if there's no file yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::File::open(path) {
+            Ok(file) => {
+                let mut profiles: Self = serde_json::from_reader(io::BufReader::new(file))?;
+                let count = profiles.profiles.len();
+                for selected in &mut profiles.selected {
+                    *selected = selected.filter(|&index| index < count);
+                }
+                Ok(profiles)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = fs::File::create(path)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn save_and_report(&self) {
+        if !ALLOW_EXPORT {
+            return;
+        }
+        if let Err(e) = self.save(Path::new(PROFILES_FILE)) {
+            warn!("Failed to save player profiles to {}: {}", PROFILES_FILE, e);
+        }
+    }
+
+    /// Profiles picked for each player.
+    pub fn selection(&self) -> Vec<Option<PlayerProfile>> {
+        self.selected
+            .iter()
+            .map(|index| index.and_then(|index| self.profiles.get(index).cloned()))
+            .collect()
+    }
+
+    /// Give an id to every profile without one, returning whether any were missing.
+    pub fn assign_missing_ids(&mut self) -> bool {
+        let mut assigned = false;
+        for profile in self.profiles.iter_mut().filter(|profile| profile.id == 0) {
+            profile.id = fastrand::u64(1..);
+            assigned = true;
+        }
+        assigned
+    }
+
+    /// Add a profile with a name nobody has yet, returning its index.
+    pub fn add(&mut self) -> usize {
+        let name = (1..)
+            .map(|number| format!("Player {}", number))
+            .find(|name| self.profiles.iter().all(|profile| &profile.name != name))
+            .unwrap();
+        self.profiles.push(PlayerProfile::new(name));
+        self.profiles.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.profiles.remove(index);
+        for selected in &mut self.selected {
+            *selected = match *selected {
+                Some(i) if i == index => None,
+                Some(i) if i > index => Some(i - 1),
+                other => other,
+            };
+        }
+    }
+
+    /// Why the profiles can't be saved as they are, if they can't.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, profile) in self.profiles.iter().enumerate() {
+            let name = profile.name.trim();
+            if name.is_empty() {
+                return Err("Every profile needs a name.".into());
+            }
+            if self.profiles[..i].iter().any(|other| other.name.trim() == name) {
+                return Err(format!("There's more than one profile named {}.", name));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use bevy::prelude::*;
+use bevy::app::AppExit;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState,
+    game::{Controller, GameState, MatchConfig, PaddleInput},
+    sim::SimulationAppExt,
+};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_enter_system(AppState::InGame, start_recording)
+            .add_exit_system(AppState::InGame, finish_replay)
+            .add_simulation_system(playback_input
+                .run_in_state(AppState::InGame)
+                .run_if_resource_exists::<ReplayPlayback>()
+                .label("paddle_input"))
+            .add_simulation_system(record_input
+                .run_in_state(AppState::InGame)
+                .run_if_resource_exists::<ReplayRecorder>()
+                .after("paddle_input")
+                .before("paddle_control"))
+            .add_system_to_stage(CoreStage::Last, save_replay_on_exit.run_if_resource_exists::<ReplayRecorder>());
+    }
+}
+
+/// Everything needed to reproduce a match: its settings, including the seed, and every paddle
+/// input, tick by tick.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub config: MatchConfig,
+    /// Every paddle's input for each tick, in the order given by `player_index`.
+    pub inputs: Vec<Vec<PaddleInput>>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        Ok(serde_json::from_reader(io::BufReader::new(file))?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = fs::File::create(path)?;
+        serde_json::to_writer(io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
+
+/// When present, the next match is saved to this file once it ends.
+pub struct RecordReplay(pub PathBuf);
+
+/// When present, matches play back this replay instead of reading live input.
+pub struct ReplayPlayback(pub Replay);
+
+/// Replay of the match currently being recorded.
+struct ReplayRecorder(Replay);
+
+fn start_recording(
+    mut commands: Commands,
+    record: Option<Res<RecordReplay>>,
+    config: Res<MatchConfig>,
+) {
+    if record.is_some() {
+        commands.insert_resource(ReplayRecorder(Replay { config: config.clone(), inputs: Vec::new() }));
+    }
+}
+
+fn record_input(
+    mut recorder: ResMut<ReplayRecorder>,
+    input_q: Query<&PaddleInput>,
+    game_state: Res<GameState>,
+) {
+    let inputs = game_state.paddles
+        .iter()
+        .map(|&entity| input_q.get(entity).copied().unwrap_or_default())
+        .collect();
+    // Ticks can be simulated again after a rollback, so overwrite rather than append.
+    recorder.0.inputs.truncate(game_state.tick as usize);
+    recorder.0.inputs.push(inputs);
+}
+
+fn playback_input(
+    mut commands: Commands,
+    playback: Res<ReplayPlayback>,
+    mut input_q: Query<(&Controller, &mut PaddleInput)>,
+    game_state: Res<GameState>,
+) {
+    let inputs = match playback.0.inputs.get(game_state.tick as usize) {
+        Some(inputs) => inputs,
+        None => {
+            // The recording is over.
+            commands.insert_resource(NextState(AppState::MainMenu));
+            return;
+        }
+    };
+
+    for (&entity, paddle_input) in game_state.paddles.iter().zip(inputs) {
+        if let Ok((Controller::Replay, mut input)) = input_q.get_mut(entity) {
+            *input = *paddle_input;
+        }
+    }
+}
+
+fn save_replay(recorder: &ReplayRecorder, record: &RecordReplay, game_state: &GameState) {
+    // The seed may have been picked at random when the match started.
+    let replay = Replay {
+        config: MatchConfig {
+            seed: Some(game_state.seed),
+            ..recorder.0.config.clone()
+        },
+        inputs: recorder.0.inputs.clone(),
+    };
+    match replay.save(&record.0) {
+        Ok(()) => info!("Saved replay to {}", record.0.display()),
+        Err(e) => warn!("Failed to save replay to {}: {}", record.0.display(), e),
+    }
+}
+
+fn finish_replay(
+    mut commands: Commands,
+    recorder: Option<Res<ReplayRecorder>>,
+    record: Option<Res<RecordReplay>>,
+    game_state: Res<GameState>,
+) {
+    if let (Some(recorder), Some(record)) = (recorder, record) {
+        save_replay(&recorder, &record, &game_state);
+        // Only the one match is recorded.
+        commands.remove_resource::<RecordReplay>();
+    }
+    commands.remove_resource::<ReplayRecorder>();
+    commands.remove_resource::<ReplayPlayback>();
+}
+
+fn save_replay_on_exit(
+    mut exit: EventReader<AppExit>,
+    recorder: Res<ReplayRecorder>,
+    record: Option<Res<RecordReplay>>,
+    game_state: Res<GameState>,
+) {
+    if exit.iter().next().is_some() {
+        if let Some(record) = record {
+            save_replay(&recorder, &record, &game_state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Handicap, PlayerSide};
+
+    #[test]
+    fn save_and_load() {
+        let mut config = MatchConfig { seed: Some(42), points_to_win: Some(7), spin: 30.0, ..default() };
+        config.handicaps[PlayerSide::Right.index()] = Handicap { paddle_length: 0.8, ..default() };
+        let inputs = vec![
+            vec![PaddleInput { movement: 1, action: false }, PaddleInput::default()],
+            vec![PaddleInput { movement: -1, action: true }, PaddleInput { movement: 1, action: false }],
+        ];
+        let replay = Replay { config, inputs };
+
+        let path = std::env::temp_dir().join(format!("bevy-pong-replay-{}.json", std::process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.inputs, replay.inputs);
+        assert_eq!(loaded.config.seed, Some(42));
+        assert_eq!(loaded.config.points_to_win, Some(7));
+        assert_eq!(loaded.config.spin, 30.0);
+        assert_eq!(loaded.config.handicaps, replay.config.handicaps);
+        assert_eq!(loaded.config.power_ups, replay.config.power_ups);
+        assert_eq!(loaded.config.arena.name, replay.config.arena.name);
+    }
+}
@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+use bevy::ecs::schedule::IntoSystemDescriptor;
+use bevy::transform::TransformSystem;
+use iyes_loopless::prelude::*;
+
+use crate::AppState;
+
+/// How many times per second the gameplay simulation is stepped.
+pub const TICK_RATE: u32 = 60;
+/// Length of a single simulation tick, in seconds.
+pub const TICK_DELTA: f32 = 1.0 / TICK_RATE as f32;
+/// Never try to catch up on more than this much time in a single frame.
+const MAX_FRAME_TIME: f32 = 0.25;
+/// How many ticks to run per frame when unthrottled.
+const UNTHROTTLED_TICKS: u32 = 1000;
+
+/// Steps all gameplay systems at a fixed rate, independent of the frame rate, and interpolates
+/// the rendered positions between the last two ticks.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = Schedule::default()
+            .with_stage(SimulationStage::Update, SystemStage::single_threaded());
+        app
+            .insert_resource(SimulationSchedule(schedule))
+            .init_resource::<SimulationClock>()
+            // Added right before `CoreStage::Update`, so after any state transitions already added.
+            .add_stage_before(CoreStage::Update, SimulationStage::Driver, SystemStage::single_threaded())
+            .add_system_to_stage(SimulationStage::Driver, run_simulation.exclusive_system())
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate_transforms.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub enum SimulationStage {
+    /// The stage in the main schedule that steps the simulation.
+    Driver,
+    /// The stage inside the simulation schedule that gameplay systems are added to.
+    Update,
+}
+
+/// Schedule holding all gameplay systems. Run once per tick by `run_simulation`.
+pub struct SimulationSchedule(pub Schedule);
+
+impl SimulationSchedule {
+    /// Simulate a single tick without advancing the clock. Also used to resimulate ticks after
+    /// rolling back to an earlier state.
+    pub fn run_tick(&mut self, world: &mut World) {
+        store_previous_positions(world);
+        self.0.run_once(world);
+    }
+}
+
+/// Keeps track of how much real time still needs to be simulated.
+#[derive(Default)]
+pub struct SimulationClock {
+    /// Number of ticks simulated so far.
+    pub tick: u64,
+    /// Run a large batch of ticks every frame, however much time passed. For simulating as fast
+    /// as possible without a window.
+    pub unthrottled: bool,
+    /// When set, stop simulating once `tick` reaches this, e.g. while waiting on a network peer.
+    pub tick_limit: Option<u64>,
+    /// Stop simulating until unpaused, without catching up on the time missed.
+    pub paused: bool,
+    /// Set while ticks already simulated once are run again after a rollback, so that anything
+    /// seen or heard outside the simulation isn't repeated.
+    pub resimulating: bool,
+    /// Frame time not yet consumed by a tick.
+    accumulator: f32,
+}
+
+impl SimulationClock {
+    /// How far between the previous and the current tick the rendered frame is, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / TICK_DELTA).min(1.0)
+    }
+}
+
+pub trait SimulationAppExt {
+    /// Add a system that runs once per simulation tick.
+    fn add_simulation_system<Params>(&mut self, system: impl IntoSystemDescriptor<Params>) -> &mut Self;
+}
+
+impl SimulationAppExt for App {
+    fn add_simulation_system<Params>(&mut self, system: impl IntoSystemDescriptor<Params>) -> &mut Self {
+        self.world
+            .resource_mut::<SimulationSchedule>()
+            .0
+            .add_system_to_stage(SimulationStage::Update, system);
+        self
+    }
+}
+
+/// Position of a simulated entity, as of the latest tick.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Position(pub Vec2);
+
+/// Position of a simulated entity as of the tick before the latest one. Used to smoothly render
+/// entities between ticks.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct PreviousPosition(pub Vec2);
+
+fn run_simulation(world: &mut World) {
+    let delta = world.resource::<Time>().delta_seconds();
+    world.resource_scope(|world, mut schedule: Mut<SimulationSchedule>| {
+        {
+            let mut clock = world.resource_mut::<SimulationClock>();
+            if clock.paused {
+                return;
+            }
+            clock.accumulator = if clock.unthrottled {
+                TICK_DELTA * UNTHROTTLED_TICKS as f32
+            } else {
+                (clock.accumulator + delta).min(MAX_FRAME_TIME)
+            };
+        }
+
+        while world.resource::<SimulationClock>().accumulator >= TICK_DELTA {
+            // Let a queued state transition happen before simulating any further.
+            if world.contains_resource::<NextState<AppState>>() {
+                break;
+            }
+            let clock = world.resource::<SimulationClock>();
+            if matches!(clock.tick_limit, Some(limit) if clock.tick >= limit) {
+                break;
+            }
+
+            schedule.run_tick(world);
+
+            let mut clock = world.resource_mut::<SimulationClock>();
+            clock.accumulator -= TICK_DELTA;
+            clock.tick += 1;
+        }
+    });
+}
+
+fn store_previous_positions(world: &mut World) {
+    let mut query = world.query::<(&Position, &mut PreviousPosition)>();
+    for (position, mut previous) in query.iter_mut(world) {
+        previous.0 = position.0;
+    }
+}
+
+fn interpolate_transforms(
+    clock: Res<SimulationClock>,
+    mut query: Query<(&Position, Option<&PreviousPosition>, &mut Transform)>,
+) {
+    let alpha = clock.alpha();
+    for (position, previous, mut transform) in query.iter_mut() {
+        let translation = match previous {
+            Some(previous) => previous.0.lerp(position.0, alpha),
+            None => position.0,
+        };
+        transform.translation.x = translation.x;
+        transform.translation.y = translation.y;
+    }
+}
@@ -0,0 +1,288 @@
+use std::{fs, io, path::Path};
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    AppState,
+    game::{ALL_SIDES, BallBouncedEvent, BallMissedEvent, GameState, LifeLostEvent, MatchOverEvent, PlayerScoredEvent, PlayerSide},
+    net::RollbackAppExt,
+    sim::{SimulationAppExt, TICK_RATE},
+};
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MatchStats>()
+            .add_rollback_resource::<MatchStats>()
+            .add_enter_system(AppState::InGame, reset_stats)
+            .add_simulation_system(track_hits
+                .run_in_state(AppState::InGame)
+                .after("ball_paddle_bounce"))
+            // Needs to see which side was serving before the next serve.
+            .add_simulation_system(track_points
+                .run_in_state(AppState::InGame)
+                .after("check_scored")
+                .before("reset_round"))
+            .add_simulation_system(track_time
+                .run_in_state(AppState::InGame)
+                .after("check_match_over"))
+            .add_simulation_system(track_scores
+                .run_in_state(AppState::InGame)
+                .after("check_match_over"))
+            .add_simulation_system(track_winner
+                .run_in_state(AppState::InGame)
+                .after("check_match_over"));
+    }
+}
+
+/// How one player did in a match.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct PlayerStats {
+    /// Score on the scoreboard, counting bricks and any head start from a handicap. Lives left
+    /// in modes that count them.
+    pub points: u32,
+    /// Times the player hit the ball with their paddle.
+    pub hits: u32,
+    /// Goals let in, each costing a life in a four-player match.
+    pub conceded: u32,
+}
+
+/// Points played with the ball served toward one side.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct ServeSideStats {
+    pub points: u32,
+    /// How many of those points the side receiving the serve won.
+    pub won_by_receiver: u32,
+}
+
+/// Statistics for the match in progress, or the last one played.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct MatchStats {
+    /// Number of paddle hits in each finished rally.
+    pub rallies: Vec<u32>,
+    /// Number of paddle hits so far in the rally in play.
+    pub current_rally: u32,
+    /// Fastest the ball went after a paddle hit.
+    pub top_ball_speed: f32,
+    /// Ticks simulated so far.
+    pub ticks: u64,
+    pub left: PlayerStats,
+    pub right: PlayerStats,
+    pub top: PlayerStats,
+    pub bottom: PlayerStats,
+    pub served_left: ServeSideStats,
+    pub served_right: ServeSideStats,
+    pub served_top: ServeSideStats,
+    pub served_bottom: ServeSideStats,
+    /// Side that won the match, once it's over. Not set for matches stopped early.
+    pub winner: Option<PlayerSide>,
+}
+
+impl MatchStats {
+    pub fn player(&self, side: PlayerSide) -> &PlayerStats {
+        match side {
+            PlayerSide::Left => &self.left,
+            PlayerSide::Right => &self.right,
+            PlayerSide::Top => &self.top,
+            PlayerSide::Bottom => &self.bottom,
+        }
+    }
+
+    fn player_mut(&mut self, side: PlayerSide) -> &mut PlayerStats {
+        match side {
+            PlayerSide::Left => &mut self.left,
+            PlayerSide::Right => &mut self.right,
+            PlayerSide::Top => &mut self.top,
+            PlayerSide::Bottom => &mut self.bottom,
+        }
+    }
+
+    pub fn serve_side(&self, side: PlayerSide) -> &ServeSideStats {
+        match side {
+            PlayerSide::Left => &self.served_left,
+            PlayerSide::Right => &self.served_right,
+            PlayerSide::Top => &self.served_top,
+            PlayerSide::Bottom => &self.served_bottom,
+        }
+    }
+
+    fn serve_side_mut(&mut self, side: PlayerSide) -> &mut ServeSideStats {
+        match side {
+            PlayerSide::Left => &mut self.served_left,
+            PlayerSide::Right => &mut self.served_right,
+            PlayerSide::Top => &mut self.served_top,
+            PlayerSide::Bottom => &mut self.served_bottom,
+        }
+    }
+
+    fn end_rally(&mut self) {
+        let rally = self.current_rally;
+        self.rallies.push(rally);
+        self.current_rally = 0;
+    }
+
+    pub fn longest_rally(&self) -> u32 {
+        self.rallies.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Average number of paddle hits in a finished rally.
+    pub fn average_rally(&self) -> f32 {
+        if self.rallies.is_empty() {
+            0.0
+        } else {
+            self.rallies.iter().sum::<u32>() as f32 / self.rallies.len() as f32
+        }
+    }
+
+    /// Time spent playing the match.
+    pub fn duration_seconds(&self) -> f32 {
+        self.ticks as f32 / TICK_RATE as f32
+    }
+
+    /// The side with the most points, or `None` if more than one side has that many.
+    pub fn leader(&self, sides: &[PlayerSide]) -> Option<PlayerSide> {
+        let most = sides.iter().map(|&side| self.player(side).points).max()?;
+        let mut leaders = sides.iter().filter(|&&side| self.player(side).points == most);
+        match (leaders.next(), leaders.next()) {
+            (Some(&side), None) => Some(side),
+            _ => None,
+        }
+    }
+
+    /// Everything, including each rally's length.
+    pub fn save_json(&self, path: &Path) -> io::Result<()> {
+        let file = fs::File::create(path)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// A header and a single row of totals, so that rows from several matches can be combined.
+    pub fn save_csv(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+
+    fn to_csv(&self) -> String {
+        let columns = [
+            ("duration_seconds", format!("{:.2}", self.duration_seconds())),
+            ("left_points", self.left.points.to_string()),
+            ("right_points", self.right.points.to_string()),
+            ("left_hits", self.left.hits.to_string()),
+            ("right_hits", self.right.hits.to_string()),
+            ("rallies", self.rallies.len().to_string()),
+            ("longest_rally", self.longest_rally().to_string()),
+            ("average_rally", format!("{:.2}", self.average_rally())),
+            ("top_ball_speed", format!("{:.1}", self.top_ball_speed)),
+            ("ticks", self.ticks.to_string()),
+            ("served_left_points", self.served_left.points.to_string()),
+            ("served_left_won_by_receiver", self.served_left.won_by_receiver.to_string()),
+            ("served_right_points", self.served_right.points.to_string()),
+            ("served_right_won_by_receiver", self.served_right.won_by_receiver.to_string()),
+            ("left_serves_lost", (self.served_left.points - self.served_left.won_by_receiver).to_string()),
+            ("right_serves_lost", (self.served_right.points - self.served_right.won_by_receiver).to_string()),
+            ("top_hits", self.top.hits.to_string()),
+            ("bottom_hits", self.bottom.hits.to_string()),
+            ("left_conceded", self.left.conceded.to_string()),
+            ("right_conceded", self.right.conceded.to_string()),
+            ("top_conceded", self.top.conceded.to_string()),
+            ("bottom_conceded", self.bottom.conceded.to_string()),
+        ];
+        let header: Vec<_> = columns.iter().map(|(name, _)| *name).collect();
+        let row: Vec<_> = columns.iter().map(|(_, value)| value.as_str()).collect();
+        format!("{}\n{}\n", header.join(","), row.join(","))
+    }
+}
+
+fn reset_stats(
+    mut commands: Commands,
+) {
+    commands.insert_resource(MatchStats::default());
+}
+
+fn track_hits(
+    mut bounced: EventReader<BallBouncedEvent>,
+    mut stats: ResMut<MatchStats>,
+) {
+    for event in bounced.iter() {
+        stats.player_mut(event.side).hits += 1;
+        stats.current_rally += 1;
+        stats.top_ball_speed = stats.top_ball_speed.max(event.speed);
+    }
+}
+
+fn track_points(
+    mut player_scored: EventReader<PlayerScoredEvent>,
+    mut lives_lost: EventReader<LifeLostEvent>,
+    mut missed: EventReader<BallMissedEvent>,
+    mut stats: ResMut<MatchStats>,
+    game_state: Res<GameState>,
+) {
+    for event in player_scored.iter() {
+        stats.end_rally();
+        stats.player_mut(event.0.opposite()).conceded += 1;
+
+        let serve = stats.serve_side_mut(game_state.serve);
+        serve.points += 1;
+        if event.0 == game_state.serve {
+            serve.won_by_receiver += 1;
+        }
+    }
+    // Goals that don't score for anyone, like in four-player matches and practice.
+    for side in lives_lost.iter().map(|event| event.0).chain(missed.iter().map(|event| event.0)) {
+        stats.end_rally();
+        stats.player_mut(side).conceded += 1;
+
+        // The receiver wins the point by not letting the ball in.
+        let serve = stats.serve_side_mut(game_state.serve);
+        serve.points += 1;
+        if side != game_state.serve {
+            serve.won_by_receiver += 1;
+        }
+    }
+}
+
+fn track_winner(
+    mut match_over: EventReader<MatchOverEvent>,
+    mut stats: ResMut<MatchStats>,
+) {
+    for event in match_over.iter() {
+        stats.winner = Some(event.winner);
+    }
+}
+
+fn track_scores(
+    mut stats: ResMut<MatchStats>,
+    game_state: Res<GameState>,
+) {
+    for &side in ALL_SIDES {
+        stats.player_mut(side).points = game_state.score(side) as u32;
+    }
+}
+
+fn track_time(
+    mut stats: ResMut<MatchStats>,
+) {
+    stats.ticks += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leader_across_sides() {
+        let mut stats = MatchStats::default();
+        stats.left.points = 2;
+        stats.right.points = 3;
+        stats.top.points = 5;
+        assert_eq!(stats.leader(&[PlayerSide::Left, PlayerSide::Right]), Some(PlayerSide::Right));
+        assert_eq!(stats.leader(ALL_SIDES), Some(PlayerSide::Top));
+
+        stats.bottom.points = 5;
+        assert_eq!(stats.leader(ALL_SIDES), None);
+        assert_eq!(stats.leader(&[]), None);
+    }
+}
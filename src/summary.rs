@@ -0,0 +1,160 @@
+use std::{io, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use iyes_loopless::prelude::*;
+
+use crate::{
+    ALLOW_EXPORT, AppState,
+    assets::Assets,
+    game::{MatchConfig, PlayerSide},
+    mode::GameModes,
+    stats::MatchStats,
+};
+
+/// Screen shown after a match ends, with the match's statistics. Needs `EguiPlugin`.
+pub struct SummaryPlugin;
+
+impl Plugin for SummaryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_enter_system(AppState::MatchOver, setup_summary)
+            .add_exit_system(AppState::MatchOver, despawn_summary)
+            .add_system(summary_ui.run_in_state(AppState::MatchOver));
+    }
+}
+
+fn setup_summary(
+    mut commands: Commands,
+    assets: Res<Assets>,
+    stats: Res<MatchStats>,
+    config: Res<MatchConfig>,
+) {
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+
+    // Nobody wins when playing alone, so show how well they did instead.
+    let title = match stats.winner.or_else(|| stats.leader(config.sides())) {
+        _ if config.solo => format!("BEST STREAK {}", stats.longest_rally()),
+        Some(side) if config.team_size() > 1 => format!("{} WIN", config.team_name(side).to_uppercase()),
+        Some(side) => format!("{} WINS", config.team_name(side).to_uppercase()),
+        None => "DRAW".into(),
+    };
+    let style = TextStyle {
+        font: assets.font.clone(),
+        font_size: 80.0,
+        color: Color::WHITE,
+    };
+    let alignment = TextAlignment {
+        horizontal: HorizontalAlign::Center,
+        ..default()
+    };
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(title, style, alignment),
+            transform: Transform::from_translation(Vec3::new(0.0, 230.0, 0.0)),
+            ..default()
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_summary(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<Camera>, With<Text>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Where to export the stats to, named after the current time so earlier exports aren't
+/// overwritten.
+fn export_path(extension: &str) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    PathBuf::from(format!("match-stats-{}.{}", seconds, extension))
+}
+
+fn describe_export(path: &Path, result: io::Result<()>) -> String {
+    match result {
+        Ok(()) => format!("Saved to {}", path.display()),
+        Err(e) => format!("Failed to save {}: {}", path.display(), e),
+    }
+}
+
+fn summary_ui(
+    mut commands: Commands,
+    mut ctx: ResMut<EguiContext>,
+    stats: Res<MatchStats>,
+    config: Res<MatchConfig>,
+    modes: Res<GameModes>,
+    mut export_status: Local<Option<String>>,
+) {
+    let window = egui::Window::new("Match Summary")
+        .title_bar(false)
+        .auto_sized()
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 20.0])
+        .frame(egui::Frame::none());
+    window.show(ctx.ctx_mut(), |ui| {
+        let sides = config.sides();
+        let row = |ui: &mut egui::Ui, name: &str, value: &dyn Fn(PlayerSide) -> String| {
+            ui.label(egui::RichText::new(name).size(20.0));
+            for &side in sides {
+                ui.label(egui::RichText::new(value(side)).size(20.0));
+            }
+            ui.end_row();
+        };
+        let serves = |side: PlayerSide| {
+            let serve = stats.serve_side(side);
+            format!("{} / {}", serve.won_by_receiver, serve.points)
+        };
+
+        egui::Grid::new("summary_stats").spacing([40.0, 8.0]).show(ui, |ui| {
+            row(ui, "", &|side| side.label().into());
+            if config.solo {
+                row(ui, "Misses", &|side| stats.player(side).conceded.to_string());
+            } else if modes.current(&config).counts_lives() {
+                row(ui, "Goals let in", &|side| stats.player(side).conceded.to_string());
+            } else {
+                row(ui, "Points", &|side| stats.player(side).points.to_string());
+            }
+            row(ui, "Hits", &|side| stats.player(side).hits.to_string());
+            row(ui, "Serves received won", &serves);
+        });
+        ui.add_space(16.0);
+
+        let seconds = stats.duration_seconds() as u32;
+        ui.label(egui::RichText::new(format!("Time: {}:{:02}", seconds / 60, seconds % 60)).size(20.0));
+        ui.label(egui::RichText::new(format!(
+            "Longest rally: {} hits (average {:.1})",
+            stats.longest_rally(),
+            stats.average_rally(),
+        )).size(20.0));
+        ui.label(egui::RichText::new(format!("Top ball speed: {:.0}", stats.top_ball_speed)).size(20.0));
+        ui.add_space(16.0);
+
+        ui.vertical_centered_justified(|ui| {
+            if ALLOW_EXPORT {
+                ui.horizontal(|ui| {
+                    if ui.button(egui::RichText::new("Export JSON").size(24.0)).clicked() {
+                        let path = export_path("json");
+                        *export_status = Some(describe_export(&path, stats.save_json(&path)));
+                    }
+                    if ui.button(egui::RichText::new("Export CSV").size(24.0)).clicked() {
+                        let path = export_path("csv");
+                        *export_status = Some(describe_export(&path, stats.save_csv(&path)));
+                    }
+                });
+                if let Some(status) = &*export_status {
+                    ui.label(status);
+                }
+            }
+
+            let menu = egui::RichText::new("Main Menu").size(40.0);
+            if ui.button(menu).clicked() {
+                *export_status = None;
+                commands.insert_resource(NextState(AppState::MainMenu));
+            }
+        });
+    });
+}